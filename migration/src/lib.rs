@@ -1,12 +1,36 @@
 pub use sea_orm_migration::prelude::*;
 
 mod m20220101_000001_create_table;
+mod m20240420_000001_create_persisted_query_table;
+mod m20240425_000001_add_post_status;
+mod m20240428_000001_create_post_revision_table;
+mod m20240502_000001_create_tag_tables;
+mod m20240505_000001_create_comment_table;
+mod m20240508_000001_create_post_search;
+mod m20240510_000001_add_user_role;
+mod m20240512_000001_create_post_slug_history_table;
+mod m20240515_000001_create_audit_log_table;
+mod m20240518_000001_create_api_token_table;
+mod m20240520_000001_extend_post_created_at_index;
 
 pub struct Migrator;
 
 #[async_trait::async_trait]
 impl MigratorTrait for Migrator {
     fn migrations() -> Vec<Box<dyn MigrationTrait>> {
-        vec![Box::new(m20220101_000001_create_table::Migration)]
+        vec![
+            Box::new(m20220101_000001_create_table::Migration),
+            Box::new(m20240420_000001_create_persisted_query_table::Migration),
+            Box::new(m20240425_000001_add_post_status::Migration),
+            Box::new(m20240428_000001_create_post_revision_table::Migration),
+            Box::new(m20240502_000001_create_tag_tables::Migration),
+            Box::new(m20240505_000001_create_comment_table::Migration),
+            Box::new(m20240508_000001_create_post_search::Migration),
+            Box::new(m20240510_000001_add_user_role::Migration),
+            Box::new(m20240512_000001_create_post_slug_history_table::Migration),
+            Box::new(m20240515_000001_create_audit_log_table::Migration),
+            Box::new(m20240518_000001_create_api_token_table::Migration),
+            Box::new(m20240520_000001_extend_post_created_at_index::Migration),
+        ]
     }
 }
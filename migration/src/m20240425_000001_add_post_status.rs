@@ -0,0 +1,71 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // existing posts were public, so they stay published
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Post::Table)
+                    .add_column(
+                        ColumnDef::new(Post::Status)
+                            .string()
+                            .not_null()
+                            .default("published"),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Post::Table)
+                    .add_column(ColumnDef::new(Post::PublishedAt).date_time().null())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .exec_stmt(
+                Query::update()
+                    .table(Post::Table)
+                    .value(Post::PublishedAt, Expr::col(Post::CreatedAt))
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_post_status_published_at")
+                    .table(Post::Table)
+                    .col(Post::Status)
+                    .col(Post::PublishedAt)
+                    .to_owned(),
+            ).await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(Index::drop().name("idx_post_status_published_at").to_owned())
+            .await?;
+        manager
+            .alter_table(Table::alter().table(Post::Table).drop_column(Post::PublishedAt).to_owned())
+            .await?;
+        manager
+            .alter_table(Table::alter().table(Post::Table).drop_column(Post::Status).to_owned())
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Post {
+    Table,
+    Status,
+    PublishedAt,
+    CreatedAt,
+}
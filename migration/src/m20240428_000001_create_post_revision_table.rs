@@ -0,0 +1,89 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(PostRevision::Table)
+                    .col(
+                        ColumnDef::new(PostRevision::Id)
+                            .uuid()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(PostRevision::PostId).uuid().not_null())
+                    .col(ColumnDef::new(PostRevision::UserId).uuid().not_null())
+                    .col(ColumnDef::new(PostRevision::Slug).string().null())
+                    .col(ColumnDef::new(PostRevision::Title).string().not_null())
+                    .col(ColumnDef::new(PostRevision::Content).string().not_null())
+                    .col(ColumnDef::new(PostRevision::CreatedAt).date_time().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_post_revision_post_id")
+                            .from(PostRevision::Table, PostRevision::PostId)
+                            .to(Post::Table, Post::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Restrict),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_post_revision_user_id")
+                            .from(PostRevision::Table, PostRevision::UserId)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::Restrict)
+                            .on_update(ForeignKeyAction::Restrict),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_post_revision_post_id_created_at")
+                    .table(PostRevision::Table)
+                    .col(PostRevision::PostId)
+                    .col(PostRevision::CreatedAt)
+                    .to_owned(),
+            ).await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(Index::drop().name("idx_post_revision_post_id_created_at").to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(PostRevision::Table).to_owned())
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum PostRevision {
+    Table,
+    Id,
+    PostId,
+    UserId,
+    Slug,
+    Title,
+    Content,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum Post {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum User {
+    Table,
+    Id,
+}
@@ -0,0 +1,105 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Comment::Table)
+                    .col(
+                        ColumnDef::new(Comment::Id)
+                            .uuid()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Comment::PostId).uuid().not_null())
+                    .col(ColumnDef::new(Comment::UserId).uuid().not_null())
+                    .col(ColumnDef::new(Comment::ParentId).uuid().null())
+                    .col(ColumnDef::new(Comment::Body).string().not_null())
+                    .col(
+                        ColumnDef::new(Comment::Status)
+                            .string()
+                            .not_null()
+                            .default("visible"),
+                    )
+                    .col(ColumnDef::new(Comment::CreatedAt).date_time().not_null())
+                    .col(ColumnDef::new(Comment::UpdatedAt).date_time().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_comment_post_id")
+                            .from(Comment::Table, Comment::PostId)
+                            .to(Post::Table, Post::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Restrict),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_comment_user_id")
+                            .from(Comment::Table, Comment::UserId)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::Restrict)
+                            .on_update(ForeignKeyAction::Restrict),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_comment_parent_id")
+                            .from(Comment::Table, Comment::ParentId)
+                            .to(Comment::Table, Comment::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Restrict),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_comment_post_id_parent_id_created_at")
+                    .table(Comment::Table)
+                    .col(Comment::PostId)
+                    .col(Comment::ParentId)
+                    .col(Comment::CreatedAt)
+                    .to_owned(),
+            ).await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(Index::drop().name("idx_comment_post_id_parent_id_created_at").to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(Comment::Table).to_owned())
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Comment {
+    Table,
+    Id,
+    PostId,
+    UserId,
+    ParentId,
+    Body,
+    Status,
+    CreatedAt,
+    UpdatedAt,
+}
+
+#[derive(DeriveIden)]
+enum Post {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum User {
+    Table,
+    Id,
+}
@@ -0,0 +1,50 @@
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::{ConnectionTrait, DbBackend};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+// the full-text index of posts is a standalone FTS5 table, post IDs are not rowids and may not stay stable
+const CREATE_TABLE: &str = "CREATE VIRTUAL TABLE post_fts USING fts5(post_id UNINDEXED, title, content)";
+
+const CREATE_TRIGGERS: &str = "
+CREATE TRIGGER post_fts_insert AFTER INSERT ON post BEGIN
+    INSERT INTO post_fts (post_id, title, content) VALUES (new.id, new.title, new.content);
+END;
+CREATE TRIGGER post_fts_update AFTER UPDATE OF title, content ON post BEGIN
+    UPDATE post_fts SET title = new.title, content = new.content WHERE post_id = new.id;
+END;
+CREATE TRIGGER post_fts_delete AFTER DELETE ON post BEGIN
+    DELETE FROM post_fts WHERE post_id = old.id;
+END;
+INSERT INTO post_fts (post_id, title, content) SELECT id, title, content FROM post;
+";
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        if manager.get_database_backend() != DbBackend::Sqlite {
+            return Ok(());
+        }
+        let db = manager.get_connection();
+        // without FTS5 compiled in, search falls back to LIKE matching
+        if db.execute_unprepared(CREATE_TABLE).await.is_err() {
+            return Ok(());
+        }
+        db.execute_unprepared(CREATE_TRIGGERS).await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        if manager.get_database_backend() != DbBackend::Sqlite {
+            return Ok(());
+        }
+        manager.get_connection().execute_unprepared("
+            DROP TRIGGER IF EXISTS post_fts_insert;
+            DROP TRIGGER IF EXISTS post_fts_update;
+            DROP TRIGGER IF EXISTS post_fts_delete;
+            DROP TABLE IF EXISTS post_fts;
+        ").await?;
+        Ok(())
+    }
+}
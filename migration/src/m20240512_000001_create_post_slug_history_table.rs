@@ -0,0 +1,66 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(PostSlugHistory::Table)
+                    .col(
+                        ColumnDef::new(PostSlugHistory::Slug)
+                            .string()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(PostSlugHistory::PostId).uuid().not_null())
+                    .col(ColumnDef::new(PostSlugHistory::CreatedAt).date_time().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_post_slug_history_post_id")
+                            .from(PostSlugHistory::Table, PostSlugHistory::PostId)
+                            .to(Post::Table, Post::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Restrict),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_post_slug_history_post_id")
+                    .table(PostSlugHistory::Table)
+                    .col(PostSlugHistory::PostId)
+                    .to_owned(),
+            ).await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(Index::drop().name("idx_post_slug_history_post_id").to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(PostSlugHistory::Table).to_owned())
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum PostSlugHistory {
+    Table,
+    Slug,
+    PostId,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum Post {
+    Table,
+    Id,
+}
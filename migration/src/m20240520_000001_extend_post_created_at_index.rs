@@ -0,0 +1,46 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // posts are ordered by creation time with the ID as tiebreak, which the index has to cover to spare the sort
+        manager
+            .drop_index(Index::drop().name("idx_post_created_at").to_owned())
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_post_created_at")
+                    .table(Post::Table)
+                    .col(Post::CreatedAt)
+                    .col(Post::Id)
+                    .to_owned(),
+            ).await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(Index::drop().name("idx_post_created_at").to_owned())
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_post_created_at")
+                    .table(Post::Table)
+                    .col(Post::CreatedAt)
+                    .to_owned(),
+            ).await?;
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Post {
+    Table,
+    Id,
+    CreatedAt,
+}
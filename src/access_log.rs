@@ -0,0 +1,202 @@
+// access log with a JSON line per request
+
+use std::{
+    io::{self, Write},
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+use actix_session::SessionExt;
+use actix_web::{
+    body::MessageBody,
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    HttpMessage,
+    HttpRequest,
+};
+use async_graphql::{
+    async_trait,
+    extensions::{Extension, ExtensionContext, ExtensionFactory, NextExecute},
+    Response,
+};
+use futures::future::{ready, LocalBoxFuture, Ready};
+use serde::Serialize;
+use tracing_actix_web::RequestId;
+use uuid::Uuid;
+
+/**
+Request extension with the name of the GraphQL operation of the request, for the access log.
+*/
+struct OperationName(String);
+
+/**
+Request data which the [OperationNames] extension fills with the name of the executed operation.
+*/
+#[derive(Clone, Default)]
+pub(crate) struct ExecutedOperation(Arc<Mutex<Option<String>>>);
+
+impl ExecutedOperation {
+    pub(crate) fn name(&self) -> Option<String> {
+        self.0.lock().ok().and_then(|name| name.clone())
+    }
+}
+
+/**
+Request extension with the user of a request authenticated by other means than the session.
+*/
+struct UserId(Uuid);
+
+/**
+Notes the name of the GraphQL operation of `req` for its access log line.
+*/
+pub(crate) fn record_operation(req: &HttpRequest, operation_name: Option<&str>) {
+    if let Some(operation_name) = operation_name {
+        req.extensions_mut().insert(OperationName(operation_name.to_string()));
+    }
+}
+
+/**
+Notes the user of `req` for its access log line, for a user who is not the one of the session.
+*/
+pub(crate) fn record_user(req: &HttpRequest, user_id: Uuid) {
+    req.extensions_mut().insert(UserId(user_id));
+}
+
+/**
+Extension factory which notes the name of the executed operation in the [ExecutedOperation] of a request.
+The name is the one the document declares, so a named operation is logged even when the request does not pick it by name.
+*/
+pub struct OperationNames;
+
+impl ExtensionFactory for OperationNames {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(OperationNamesExtension)
+    }
+}
+
+struct OperationNamesExtension;
+
+#[async_trait::async_trait]
+impl Extension for OperationNamesExtension {
+    // the schema passes the name of the operation it picked from the document, not the one of the request
+    async fn execute(&self, ctx: &ExtensionContext<'_>, operation_name: Option<&str>, next: NextExecute<'_>) -> Response {
+        if let (Some(name), Some(executed)) = (operation_name, ctx.data_opt::<ExecutedOperation>()) {
+            if let Ok(mut executed) = executed.0.lock() {
+                *executed = Some(name.to_string());
+            }
+        }
+        next.run(ctx, operation_name).await
+    }
+}
+
+#[derive(Serialize)]
+struct AccessLogLine<'a> {
+    method: &'a str,
+    path: &'a str,
+    status: u16,
+    latency_ms: f64,
+    request_id: Option<String>,
+    user_id: Option<String>,
+    operation_name: Option<String>,
+}
+
+/**
+Middleware writing a JSON line per request. It reads the user from the session, so wrap it before (inside)
+the [session middleware](crate::session_middleware), and the request ID of [RequestTracing](crate::telemetry::RequestTracing)
+after (outside) it.
+*/
+#[derive(Clone)]
+pub struct AccessLog {
+    writer: Arc<Mutex<dyn Write + Send>>,
+}
+
+impl AccessLog {
+    /// Writes to stdout, apart from the logs on stderr
+    pub fn stdout() -> Self {
+        Self::to(io::stdout())
+    }
+
+    pub fn to(writer: impl Write + Send + 'static) -> Self {
+        Self { writer: Arc::new(Mutex::new(writer)) }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for AccessLog
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Transform = AccessLogMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(AccessLogMiddleware { service, writer: self.writer.clone() }))
+    }
+}
+
+pub struct AccessLogMiddleware<S> {
+    service: S,
+    writer: Arc<Mutex<dyn Write + Send>>,
+}
+
+impl<S, B> Service<ServiceRequest> for AccessLogMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        // owned copies, as a clone of the request would keep the router from matching it
+        let method = req.method().to_string();
+        let path = req.path().to_string();
+        let writer = self.writer.clone();
+        let start = Instant::now();
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            let res = fut.await;
+            let line = match &res {
+                Ok(res) => {
+                    let http_req = res.request();
+                    // the session as the handler left it, so a login is logged with the new user
+                    // in its own statement, so the extensions are no longer borrowed when the session borrows them
+                    let token_user = http_req.extensions().get::<UserId>().map(|user_id| user_id.0);
+                    let user_id = token_user
+                        .or_else(|| http_req.get_session().get::<Uuid>("user_id").ok().flatten())
+                        .map(|user_id| user_id.to_string());
+                    AccessLogLine {
+                        method: &method,
+                        path: &path,
+                        status: res.status().as_u16(),
+                        latency_ms: start.elapsed().as_secs_f64() * 1000.0,
+                        request_id: http_req.extensions().get::<RequestId>().map(ToString::to_string),
+                        user_id,
+                        operation_name: http_req.extensions().get::<OperationName>().map(|name| name.0.clone()),
+                    }
+                },
+                Err(err) => AccessLogLine {
+                    method: &method,
+                    path: &path,
+                    status: err.as_response_error().status_code().as_u16(),
+                    latency_ms: start.elapsed().as_secs_f64() * 1000.0,
+                    request_id: None,
+                    user_id: None,
+                    operation_name: None,
+                },
+            };
+            if let (Ok(json), Ok(mut writer)) = (serde_json::to_string(&line), writer.lock()) {
+                // a failing log must not fail the request
+                let _ = writeln!(writer, "{}", json);
+            }
+            res
+        })
+    }
+}
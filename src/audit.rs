@@ -0,0 +1,51 @@
+// security-relevant events, recorded in the audit log for admins
+
+use anyhow::Result;
+use actix_web::HttpRequest;
+use async_graphql::Context;
+use sea_orm::{prelude::*, ActiveValue::Set, ConnectionTrait};
+
+use super::entity::{audit_log, sea_orm_active_enums::AuditEvent};
+
+/**
+Request data with the address of the client, for the audit entries of resolvers.
+*/
+pub struct ClientIp(pub Option<String>);
+
+/**
+Address of the client of `req`. The peer address rather than forwarding headers, which the client could forge.
+*/
+pub fn client_ip(req: &HttpRequest) -> Option<String> {
+    req.peer_addr().map(|addr| addr.ip().to_string())
+}
+
+/**
+Address of the client of the GraphQL request of `ctx`.
+*/
+pub fn client_ip_of_ctx(ctx: &Context<'_>) -> Option<String> {
+    ctx.data_opt::<ClientIp>().and_then(|ip| ip.0.clone())
+}
+
+/**
+Records `event`, caused by `actor_id` and about the user or post `subject_id`, in the transaction of the change.
+*/
+pub async fn record(
+    conn: &impl ConnectionTrait,
+    event: AuditEvent,
+    actor_id: Option<Uuid>,
+    subject_id: Option<Uuid>,
+    detail: Option<String>,
+    ip: Option<String>,
+) -> Result<()> {
+    let entry = audit_log::ActiveModel {
+        id: Set(Uuid::new_v4()),
+        event: Set(event),
+        actor_id: Set(actor_id),
+        subject_id: Set(subject_id),
+        detail: Set(detail),
+        ip: Set(ip),
+        created_at: Set(chrono::Utc::now().naive_utc()),
+    };
+    entry.insert(conn).await?;
+    Ok(())
+}
@@ -3,12 +3,11 @@ use uuid::Uuid;
 use chrono::{Utc, NaiveDateTime};
 use serde_json::{to_value, from_value};
 use actix_session::Session;
-use actix_web::{web, HttpResponse};
+use actix_web::{web, HttpRequest, HttpResponse};
 use sea_orm::prelude::*;
-use sea_orm::ActiveValue::Set;
+use sea_orm::{ActiveValue::Set, ConnectionTrait};
 use webauthn_rs::prelude::{
     Passkey,
-    Webauthn,
     CreationChallengeResponse,
     PasskeyRegistration,
     RegisterPublicKeyCredential,
@@ -19,76 +18,112 @@ use webauthn_rs::prelude::{
 use futures::future::FutureExt;
 
 use super::{
+    AppState,
     Error,
+    audit,
     db,
-    entity::{user, passkey},
+    telemetry,
+    entity::{user, passkey, sea_orm_active_enums::{AuditEvent, Role}},
 };
 
+/**
+The user logged in to `session`, loaded anew on every request so that changes of the user apply at once.
+A session of a user who no longer exists is purged.
+*/
+pub(crate) async fn session_user(session: &Session, conn: &impl ConnectionTrait) -> Result<Option<user::Model>> {
+    let Some(user_id) = session.get::<Uuid>("user_id")? else {
+        return Ok(None);
+    };
+    let user = user::Entity::find_by_id(user_id).one(conn).await?;
+    if user.is_none() {
+        session.purge();
+    }
+    Ok(user)
+}
 
-pub async fn start_registration(session: Session, webauthn: web::Data<Webauthn>) -> Result<web::Json<CreationChallengeResponse>, Error> {
-    let res = start_registration_anyhow_result(session, webauthn).await?;
-    Ok(res)
+// logs `user_id` in under a new session key, so that a key planted before the login is of no use, dropping leftover ceremony state
+fn log_in(session: &Session, user_id: Uuid) -> Result<()> {
+    session.clear();
+    session.insert("user_id", user_id)?;
+    session.renew();
+    Ok(())
 }
 
-async fn start_registration_anyhow_result(session: Session, webauthn: web::Data<Webauthn>) -> Result<web::Json<CreationChallengeResponse>> {
+pub async fn start_registration(http_req: HttpRequest, session: Session, state: web::Data<AppState>) -> Result<web::Json<CreationChallengeResponse>, Error> {
+    state.auth_limiter.check_ip(audit::client_ip(&http_req).as_deref())?;
+    let metrics = state.metrics.clone();
+    let res = start_registration_anyhow_result(session, state).await;
+    metrics.count_ceremony("registration", "start", res.is_ok());
+    Ok(res?)
+}
+
+async fn start_registration_anyhow_result(session: Session, state: web::Data<AppState>) -> Result<web::Json<CreationChallengeResponse>> {
     session.remove("reg_state");
 
     let user_id = Uuid::new_v4();
     let username = format!("user-{}", user_id);
-    let (ccr, reg_state) = webauthn.start_passkey_registration(user_id, &username, "New User", None)?;
+    let (ccr, reg_state) = state.webauthn.start_passkey_registration(user_id, &username, "New User", None)?;
 
     session.insert("reg_state", (user_id, reg_state))?;
     Ok(web::Json(ccr))
 }
 
-pub async fn finish_registration(req: web::Json<RegisterPublicKeyCredential>, session: Session, webauthn: web::Data<Webauthn>) -> Result<HttpResponse, Error> {
-    let res = finish_registration_anyhow_result(req, session, webauthn).await?;
-    Ok(res)
+pub async fn finish_registration(http_req: HttpRequest, req: web::Json<RegisterPublicKeyCredential>, session: Session, state: web::Data<AppState>) -> Result<HttpResponse, Error> {
+    let metrics = state.metrics.clone();
+    let res = finish_registration_anyhow_result(req, session, state, audit::client_ip(&http_req)).await;
+    metrics.count_ceremony("registration", "finish", res.is_ok());
+    Ok(res?)
 }
 
-async fn finish_registration_anyhow_result(req: web::Json<RegisterPublicKeyCredential>, session: Session, webauthn: web::Data<Webauthn>) -> Result<HttpResponse> {
+async fn finish_registration_anyhow_result(req: web::Json<RegisterPublicKeyCredential>, session: Session, state: web::Data<AppState>, ip: Option<String>) -> Result<HttpResponse> {
     let (user_id, reg_state): (Uuid, PasskeyRegistration) = match session.remove_as("reg_state") {
         None => bail!("No registration state found"),
         Some(Err(str)) => bail!("Invalid registration state: {}", str),
         Some(Ok(val)) => val,
     };
 
-    let passkey = webauthn.finish_passkey_registration(&req, &reg_state)?;
+    let passkey = state.webauthn.finish_passkey_registration(&req, &reg_state)?;
+    let credential_id = passkey.cred_id().to_string();
 
-    let conn = db::connect().await?;
-    let user = db::transaction(&conn, move |txn| async move {
+    let user = db::transaction(&state.conn, move |txn| async move {
         let now = Utc::now();
         let now: NaiveDateTime = now.naive_utc();
         let user = user::ActiveModel {
-            id: Set(user_id.clone()),
+            id: Set(user_id),
             registered_at: Set(now),
+            role: Set(Role::Reader),
             ..Default::default()
         };
         let user = user.insert(txn).await?;
         let passkey = passkey::ActiveModel {
             user_id: Set(user_id),
             content: Set(to_value(passkey)?),
-            ..Default::default()
         };
         passkey.insert(txn).await?;
+        audit::record(txn, AuditEvent::Registration, Some(user_id), Some(user_id), None, ip.clone()).await?;
+        audit::record(txn, AuditEvent::PasskeyAdded, Some(user_id), Some(user_id), Some(credential_id), ip).await?;
         Ok(user)
     }.boxed()).await?;
 
-    session.insert("user", user)?;
+    telemetry::record_user(user.id);
+    log_in(&session, user.id)?;
     Ok(HttpResponse::Ok().finish())
 }
 
-pub async fn start_authentication(user_id: web::Json<Uuid>, session: Session, webauthn: web::Data<Webauthn>) -> Result<web::Json<RequestChallengeResponse>, Error> {
-    let res = start_authentication_anyhow_result(user_id, session, webauthn).await?;
-    Ok(res)
+pub async fn start_authentication(http_req: HttpRequest, user_id: web::Json<Uuid>, session: Session, state: web::Data<AppState>) -> Result<web::Json<RequestChallengeResponse>, Error> {
+    state.auth_limiter.check_ip(audit::client_ip(&http_req).as_deref())?;
+    state.auth_limiter.check_user(*user_id)?;
+    let metrics = state.metrics.clone();
+    let res = start_authentication_anyhow_result(user_id, session, state).await;
+    metrics.count_ceremony("authentication", "start", res.is_ok());
+    Ok(res?)
 }
 
-async fn start_authentication_anyhow_result(user_id: web::Json<Uuid>, session: Session, webauthn: web::Data<Webauthn>) -> Result<web::Json<RequestChallengeResponse>> {
+async fn start_authentication_anyhow_result(user_id: web::Json<Uuid>, session: Session, state: web::Data<AppState>) -> Result<web::Json<RequestChallengeResponse>> {
     session.remove("auth_state");
     let user_id = user_id.into_inner();
 
-    let conn = db::connect().await?;
-    let passkeys = db::transaction(&conn, move |txn| async move {
+    let passkeys = db::transaction(&state.conn, move |txn| async move {
         let passkeys = passkey::Entity::find()
             .filter(passkey::Column::UserId.eq(user_id))
             .all(txn)
@@ -101,30 +136,47 @@ async fn start_authentication_anyhow_result(user_id: web::Json<Uuid>, session: S
         Ok(passkey)
     }).collect::<Result<Vec<_>>>()?;
 
-    let (rcr, auth_state) = webauthn.start_passkey_authentication(&passkeys)?;
+    let (rcr, auth_state) = state.webauthn.start_passkey_authentication(&passkeys)?;
 
     session.insert("auth_state", (user_id, auth_state))?;
 
     Ok(web::Json(rcr))
 }
 
-pub async fn finish_authentication(req: web::Json<PublicKeyCredential>, session: Session, webauthn: web::Data<Webauthn>) -> Result<HttpResponse, Error> {
-    let res = finish_authentication_anyhow_result(req, session, webauthn).await?;
-    Ok(res)
+pub async fn finish_authentication(http_req: HttpRequest, req: web::Json<PublicKeyCredential>, session: Session, state: web::Data<AppState>) -> Result<HttpResponse, Error> {
+    let ip = audit::client_ip(&http_req);
+    // the ceremony takes its state out of the session, so look up whose login this is before
+    let user_id = session.get::<(Uuid, PasskeyAuthentication)>("auth_state").ok().flatten().map(|(user_id, _)| user_id);
+    if let Some(user_id) = user_id {
+        // a lockout starting during the ceremony applies to it too
+        state.auth_limiter.check_lockout(user_id)?;
+    }
+
+    let res = finish_authentication_anyhow_result(req, session, state.clone(), ip.clone()).await;
+    state.metrics.count_ceremony("authentication", "finish", res.is_ok());
+    if let Some(user_id) = user_id {
+        match &res {
+            Ok(_) => state.auth_limiter.login_succeeded(user_id),
+            Err(_) => state.auth_limiter.login_failed(user_id),
+        }
+    }
+    if let Err(err) = &res {
+        audit::record(&state.conn, AuditEvent::LoginFailed, None, user_id, Some(err.to_string()), ip).await?;
+    }
+    Ok(res?)
 }
 
-async fn finish_authentication_anyhow_result(req: web::Json<PublicKeyCredential>, session: Session, webauthn: web::Data<Webauthn>) -> Result<HttpResponse> {
+async fn finish_authentication_anyhow_result(req: web::Json<PublicKeyCredential>, session: Session, state: web::Data<AppState>, ip: Option<String>) -> Result<HttpResponse> {
     let (user_id, auth_state): (Uuid, PasskeyAuthentication) = match session.remove_as("auth_state") {
         None => bail!("No authentication state found"),
         Some(Err(str)) => bail!("Invalid authentication state: {}", str),
         Some(Ok(val)) => val,
     };
 
-    let auth_result = webauthn.finish_passkey_authentication(&req, &auth_state)?;
+    let auth_result = state.webauthn.finish_passkey_authentication(&req, &auth_state)?;
     let user_verified = auth_result.user_verified();
 
-    let conn = db::connect().await?;
-    db::transaction(&conn, move |txn| async move {
+    db::transaction(&state.conn, move |txn| async move {
         let passkeys = passkey::Entity::find()
             .filter(passkey::Column::UserId.eq(user_id))
             .all(txn)
@@ -144,12 +196,16 @@ async fn finish_authentication_anyhow_result(req: web::Json<PublicKeyCredential>
         bail!("Authentication failed");
     }
 
-    let user = db::transaction(&conn, move |txn| async move {
-        let user = user::Entity::find_by_id(user_id).one(txn).await?;
+    let user = db::transaction(&state.conn, move |txn| async move {
+        let Some(user) = user::Entity::find_by_id(user_id).one(txn).await? else {
+            bail!("User not found");
+        };
+        audit::record(txn, AuditEvent::LoginSucceeded, Some(user_id), Some(user_id), None, ip).await?;
         Ok(user)
     }.boxed()).await?;
 
-    session.insert("user", user)?;
+    telemetry::record_user(user.id);
+    log_in(&session, user.id)?;
     Ok(HttpResponse::Ok().finish())
 }
 
@@ -0,0 +1,134 @@
+// CSRF protection of the cookie-authenticated endpoints: JSON requests from allowed origins, or a double-submit token
+
+use actix_web::{
+    body::MessageBody,
+    cookie::{Cookie, SameSite},
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    error::ErrorForbidden,
+    http::{header, Method},
+    web,
+    HttpMessage,
+    HttpRequest,
+    HttpResponse,
+};
+use futures::future::{ready, LocalBoxFuture, Ready};
+use rand::distributions::{Alphanumeric, DistString};
+use serde_json::json;
+use url::Url;
+
+use super::{constant_time_eq, AppState};
+
+pub const COOKIE_NAME: &str = "csrf_token";
+pub const HEADER_NAME: &str = "x-csrf-token";
+
+/**
+Origins allowed to send requests with the session cookie, configurable from the command line.
+*/
+#[derive(Debug, Clone, Default, clap::Args)]
+pub struct CsrfConfig {
+    /// Origin whose pages may send requests with the session cookie, besides the relying party's, repeatable
+    #[clap(long = "allowed-origin")]
+    pub allowed_origins: Vec<Url>,
+}
+
+/**
+Whether the `Origin`, or else the `Referer`, of `req` is one of `allowed_origins`. Requests with neither are not
+from a page of another origin, as browsers send an `Origin` with every cross-origin POST and WebSocket handshake.
+*/
+pub(crate) fn origin_allowed(req: &HttpRequest, allowed_origins: &[String]) -> bool {
+    let origin = if let Some(origin) = req.headers().get(header::ORIGIN) {
+        origin.to_str().ok().map(str::to_string)
+    } else if let Some(referer) = req.headers().get(header::REFERER) {
+        referer.to_str().ok()
+            .and_then(|referer| Url::parse(referer).ok())
+            .map(|referer| referer.origin().ascii_serialization())
+    } else {
+        return true;
+    };
+    // an opaque origin serializes as "null", which is never allowed
+    origin.is_some_and(|origin| allowed_origins.contains(&origin))
+}
+
+// whether the token of the `x-csrf-token` header matches the one of the cookie
+fn token_matches(req: &HttpRequest) -> bool {
+    let Some(cookie) = req.cookie(COOKIE_NAME) else {
+        return false;
+    };
+    let header = req.headers().get(HEADER_NAME).and_then(|header| header.to_str().ok());
+    !cookie.value().is_empty() && header.is_some_and(|header| constant_time_eq(header.as_bytes(), cookie.value().as_bytes()))
+}
+
+/**
+Sets the cookie of the double-submit token and returns the token, for clients sending form-encoded or multipart requests.
+They send it back in the `x-csrf-token` header, which the pages of other origins cannot set.
+*/
+pub(crate) async fn issue_token(req: HttpRequest) -> HttpResponse {
+    let token = req.cookie(COOKIE_NAME)
+        .map(|cookie| cookie.value().to_string())
+        .filter(|token| !token.is_empty())
+        .unwrap_or_else(|| Alphanumeric.sample_string(&mut rand::thread_rng(), 32));
+    let cookie = Cookie::build(COOKIE_NAME, token.clone())
+        .path("/")
+        .same_site(SameSite::Strict)
+        .finish();
+    HttpResponse::Ok().cookie(cookie).json(json!({ "token": token }))
+}
+
+/**
+Middleware rejecting unsafe requests with `403 Forbidden` unless they are JSON from an allowed origin,
+or carry the double-submit token. Pages of other origins cannot send JSON without a CORS preflight,
+which is not granted, but they can send forms and `text/plain` bodies, which GraphQL would parse as JSON.
+*/
+pub struct CsrfProtection;
+
+impl<S, B> Transform<S, ServiceRequest> for CsrfProtection
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Transform = CsrfProtectionMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(CsrfProtectionMiddleware { service }))
+    }
+}
+
+pub struct CsrfProtectionMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for CsrfProtectionMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let safe = matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS);
+        // a bearer token is not sent along by browsers, and pages of other origins cannot set the header without a preflight
+        let bearer = req.headers().contains_key(header::AUTHORIZATION);
+        if !safe && !bearer {
+            let allowed_origins = req.app_data::<web::Data<AppState>>()
+                .map(|state| state.allowed_origins.as_slice())
+                .unwrap_or_default();
+            if !origin_allowed(req.request(), allowed_origins) {
+                return Box::pin(ready(Err(ErrorForbidden("cross-origin request"))));
+            }
+            if req.content_type() != "application/json" && !token_matches(req.request()) {
+                return Box::pin(ready(Err(ErrorForbidden("missing or invalid CSRF token"))));
+            }
+        }
+        Box::pin(self.service.call(req))
+    }
+}
@@ -10,8 +10,10 @@ use sea_orm::{
 };
 use futures::{Future, FutureExt};    
 
-pub async fn connect() -> Result<DatabaseConnection> {
-    let conn_opt = ConnectOptions::new("sqlite:db/main.db");
+pub const DEFAULT_URL: &str = "sqlite:db/main.db";
+
+pub async fn connect(url: &str) -> Result<DatabaseConnection> {
+    let conn_opt = ConnectOptions::new(url);
     let conn = Database::connect(conn_opt).await?;
     Ok(conn)
 }
@@ -1,5 +1,14 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.12.15
 
+pub mod api_token;
+pub mod audit_log;
+pub mod comment;
 pub mod passkey;
+pub mod persisted_query;
 pub mod post;
+pub mod post_revision;
+pub mod post_slug_history;
+pub mod post_tag;
+pub mod sea_orm_active_enums;
+pub mod tag;
 pub mod user;
@@ -1,8 +1,11 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.12.15
 
 use sea_orm::entity::prelude::*;
-use async_graphql::{SimpleObject, ComplexObject};
+use async_graphql::{connection::Connection, SimpleObject, ComplexObject, Context};
 use chrono::NaiveDateTime;
+use sea_orm::{Condition, QueryOrder, QuerySelect};
+
+use super::sea_orm_active_enums::PostStatus;
 
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, SimpleObject)]
 #[sea_orm(table_name = "post")]
@@ -19,6 +22,9 @@ pub struct Model {
     pub content: String,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
+    pub status: PostStatus,
+    /// When a published or archived post was last published, or when a scheduled post will be. Null for drafts.
+    pub published_at: Option<NaiveDateTime>,
 }
 
 #[ComplexObject]
@@ -30,6 +36,73 @@ impl Model {
     pub async fn user_id(&self) -> String {
         self.id.to_string()
     }
+
+    /// The content rendered from Markdown to sanitized HTML
+    pub async fn html(&self) -> String {
+        crate::markdown::cached_html(self.id, self.updated_at, &self.content).to_string()
+    }
+
+    /// The beginning of the content as plain text
+    pub async fn excerpt(&self, #[graphql(default = 200)] length: usize) -> String {
+        crate::markdown::excerpt(&self.content, length)
+    }
+
+    /// Estimated reading time in minutes
+    pub async fn reading_time(&self) -> usize {
+        crate::markdown::reading_time(&self.content)
+    }
+
+    /// Tags of the post, ordered by slug
+    pub async fn tags(&self, ctx: &Context<'_>) -> anyhow::Result<Vec<super::tag::Model>> {
+        let trx = crate::trx_from_ctx(ctx)?;
+        let tags = self.find_related(super::tag::Entity)
+            .order_by_asc(super::tag::Column::Slug)
+            .all(trx.as_ref())
+            .await?;
+        Ok(tags)
+    }
+
+    /// Top level comments, oldest first, with their replies nested below them. Hidden comments are left out for
+    /// everyone but their author and the post author.
+    #[graphql(complexity = "crate::pagination::complexity(first, child_complexity)")]
+    pub async fn comments(&self, ctx: &Context<'_>, first: Option<i32>, after: Option<String>) -> anyhow::Result<Connection<usize, super::comment::Model>> {
+        let page = crate::pagination::Page::new(first, after)?;
+        let trx = crate::trx_from_ctx(ctx)?;
+        let comments = self.find_related(super::comment::Entity)
+            .filter(super::comment::Column::ParentId.is_null())
+            .filter(super::comment::visible_to(ctx.data_opt::<super::user::Model>(), self.user_id))
+            .order_by_asc(super::comment::Column::CreatedAt)
+            .offset(page.offset)
+            .limit(page.fetch_limit())
+            .all(trx.as_ref())
+            .await?;
+        Ok(page.connection(comments))
+    }
+
+    /// Every saved state of the post, oldest first. Only the author can read them.
+    pub async fn revisions(&self, ctx: &Context<'_>) -> anyhow::Result<Vec<super::post_revision::Model>> {
+        self.check_author(ctx)?;
+        let trx = crate::trx_from_ctx(ctx)?;
+        crate::revision::list(&trx, self.id).await
+    }
+
+    /// Unified diff from one revision of the post to another
+    pub async fn revision_diff(&self, ctx: &Context<'_>, from: String, to: String) -> anyhow::Result<String> {
+        self.check_author(ctx)?;
+        let trx = crate::trx_from_ctx(ctx)?;
+        let from = crate::revision::find(&trx, self.id, &from).await?;
+        let to = crate::revision::find(&trx, self.id, &to).await?;
+        Ok(crate::revision::unified_diff(&from, &to))
+    }
+}
+
+impl Model {
+    fn check_author(&self, ctx: &Context<'_>) -> anyhow::Result<()> {
+        match ctx.data_opt::<super::user::Model>() {
+            Some(user) if user.id == self.user_id => Ok(()),
+            _ => Err(anyhow::anyhow!("forbidden")),
+        }
+    }
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -42,6 +115,48 @@ pub enum Relation {
         on_delete = "Restrict"
     )]
     User,
+    #[sea_orm(has_many = "super::comment::Entity")]
+    Comment,
+    #[sea_orm(has_many = "super::post_revision::Entity")]
+    PostRevision,
+    #[sea_orm(has_many = "super::post_slug_history::Entity")]
+    PostSlugHistory,
+    #[sea_orm(has_many = "super::post_tag::Entity")]
+    PostTag,
+}
+
+impl Related<super::comment::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Comment.def()
+    }
+}
+
+impl Related<super::post_revision::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::PostRevision.def()
+    }
+}
+
+impl Related<super::post_slug_history::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::PostSlugHistory.def()
+    }
+}
+
+impl Related<super::post_tag::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::PostTag.def()
+    }
+}
+
+impl Related<super::tag::Entity> for Entity {
+    fn to() -> RelationDef {
+        super::post_tag::Relation::Tag.def()
+    }
+
+    fn via() -> Option<RelationDef> {
+        Some(super::post_tag::Relation::Post.def().rev())
+    }
 }
 
 impl Related<super::user::Entity> for Entity {
@@ -51,3 +166,14 @@ impl Related<super::user::Entity> for Entity {
 }
 
 impl ActiveModelBehavior for ActiveModel {}
+
+/**
+Condition selecting the posts `user` may read: published ones, plus their own in any status.
+*/
+pub fn visible_to(user: Option<&super::user::Model>) -> Condition {
+    let published = Column::Status.eq(PostStatus::Published);
+    match user {
+        Some(user) => Condition::any().add(published).add(Column::UserId.eq(user.id)),
+        None => Condition::all().add(published),
+    }
+}
@@ -1,9 +1,14 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.12.15
 
 use sea_orm::entity::prelude::*;
-use async_graphql::{SimpleObject, ComplexObject};
+use sea_orm::QueryOrder;
+use async_graphql::{SimpleObject, ComplexObject, Context};
 use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
+use webauthn_rs::prelude::Passkey;
+
+use super::sea_orm_active_enums::Role;
+use crate::role_guard::{RoleGuard, SelfGuard};
 
 #[derive(Clone, Debug, PartialEq, Deserialize, Serialize, DeriveEntityModel, Eq, SimpleObject)]
 #[sea_orm(table_name = "user")]
@@ -15,8 +20,39 @@ pub struct Model {
     #[sea_orm(unique)]
     pub slug: Option<String>,
     pub name: Option<String>,
+    #[graphql(skip)]
     pub comment: Option<String>,
+    #[graphql(skip)]
     pub registered_at: NaiveDateTime,
+    #[graphql(skip)]
+    pub role: Role,
+}
+
+/**
+A passkey registered by a user.
+*/
+#[derive(SimpleObject)]
+pub struct PasskeyInfo {
+    /// The WebAuthn credential ID, base64url encoded
+    pub credential_id: String,
+}
+
+impl TryFrom<super::passkey::Model> for PasskeyInfo {
+    type Error = anyhow::Error;
+
+    fn try_from(passkey: super::passkey::Model) -> anyhow::Result<Self> {
+        let passkey: Passkey = serde_json::from_value(passkey.content)?;
+        let credential_id = serde_json::to_value(passkey.cred_id())?;
+        Ok(Self { credential_id: credential_id.as_str().unwrap_or_default().to_string() })
+    }
+}
+
+/**
+A session in which a user is logged in.
+*/
+#[derive(SimpleObject)]
+pub struct SessionInfo {
+    pub valid_until: NaiveDateTime,
 }
 
 #[ComplexObject]
@@ -24,14 +60,76 @@ impl Model {
     pub async fn id(&self) -> String {
         self.id.to_string()
     }
+
+    /// Note about the user. Only visible to admins.
+    #[graphql(guard = "RoleGuard::new(Role::Admin)")]
+    pub async fn comment(&self) -> Option<String> {
+        self.comment.clone()
+    }
+
+    /// Only visible to the user themselves and admins
+    #[graphql(guard = "SelfGuard::new(self.id).or(RoleGuard::new(Role::Admin))")]
+    pub async fn registered_at(&self) -> Option<NaiveDateTime> {
+        Some(self.registered_at)
+    }
+
+    /// Only visible to the user themselves and admins
+    #[graphql(guard = "SelfGuard::new(self.id).or(RoleGuard::new(Role::Admin))")]
+    pub async fn role(&self) -> Option<Role> {
+        Some(self.role)
+    }
+
+    /// Only visible to the user themselves
+    #[graphql(guard = "SelfGuard::new(self.id)")]
+    pub async fn passkeys(&self, ctx: &Context<'_>) -> anyhow::Result<Vec<PasskeyInfo>> {
+        let trx = crate::trx_from_ctx(ctx)?;
+        let passkeys = self.find_related(super::passkey::Entity).all(trx.as_ref()).await?;
+        passkeys.into_iter().map(PasskeyInfo::try_from).collect()
+    }
+
+    /// Only visible to the user themselves
+    #[graphql(guard = "SelfGuard::new(self.id)")]
+    pub async fn sessions(&self) -> Vec<SessionInfo> {
+        crate::session::valid_until_of_user(self.id).into_iter()
+            .map(|valid_until| SessionInfo { valid_until: valid_until.naive_utc() })
+            .collect()
+    }
+
+    /// The API tokens of the user, expired ones included. Only visible to the user themselves
+    #[graphql(guard = "SelfGuard::new(self.id)")]
+    pub async fn api_tokens(&self, ctx: &Context<'_>) -> anyhow::Result<Vec<super::api_token::Model>> {
+        let trx = crate::trx_from_ctx(ctx)?;
+        let api_tokens = self.find_related(super::api_token::Entity)
+            .order_by_asc(super::api_token::Column::CreatedAt)
+            .all(trx.as_ref()).await?;
+        Ok(api_tokens)
+    }
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_many = "super::api_token::Entity")]
+    ApiToken,
+    #[sea_orm(has_many = "super::comment::Entity")]
+    Comment,
     #[sea_orm(has_many = "super::passkey::Entity")]
     Passkey,
     #[sea_orm(has_many = "super::post::Entity")]
     Post,
+    #[sea_orm(has_many = "super::post_revision::Entity")]
+    PostRevision,
+}
+
+impl Related<super::api_token::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ApiToken.def()
+    }
+}
+
+impl Related<super::comment::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Comment.def()
+    }
 }
 
 impl Related<super::passkey::Entity> for Entity {
@@ -46,4 +144,10 @@ impl Related<super::post::Entity> for Entity {
     }
 }
 
+impl Related<super::post_revision::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::PostRevision.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,132 @@
+// GraphQL over HTTP GET: query operations only, with cache validators computed from the response body
+// see https://graphql.github.io/graphql-over-http/draft/#sec-GET
+
+use std::sync::Arc;
+use anyhow::Result;
+use actix_web::{HttpRequest, HttpResponse};
+use actix_web::http::header::{self, EntityTag, ETag, Header, IfNoneMatch};
+use async_graphql::{
+    async_trait,
+    extensions::{Extension, ExtensionContext, ExtensionFactory, NextParseQuery},
+    parser::types::{ExecutableDocument, OperationType},
+    Response,
+    ServerError,
+    ServerResult,
+    Value,
+    Variables,
+};
+use sha2::{Digest, Sha256};
+
+pub const CODE_METHOD_NOT_ALLOWED: &str = "METHOD_NOT_ALLOWED";
+
+const CONTENT_TYPE: &str = "application/graphql-response+json";
+
+// responses depend on the viewer, who is told by either the session cookie or a bearer token
+const VARY: &str = "Cookie, Authorization";
+
+/**
+Request data marking a GraphQL request which came in over GET.
+*/
+pub struct GetRequest {
+    pub operation_name: Option<String>,
+}
+
+/**
+The `operationName` parameter of a GET request. The query string parser of async-graphql only knows `operation_name`,
+so the name the spec uses would be dropped otherwise.
+*/
+pub fn operation_name(req: &HttpRequest) -> Option<String> {
+    url::form_urlencoded::parse(req.query_string().as_bytes())
+        .find(|(key, _)| key == "operationName")
+        .map(|(_, value)| value.into_owned())
+}
+
+/**
+Extension factory which rejects anything but query operations in requests marked with [GetRequest].
+*/
+pub struct QueryOnlyOverGet;
+
+impl ExtensionFactory for QueryOnlyOverGet {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(QueryOnlyOverGetExtension)
+    }
+}
+
+struct QueryOnlyOverGetExtension;
+
+#[async_trait::async_trait]
+impl Extension for QueryOnlyOverGetExtension {
+    async fn parse_query(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        query: &str,
+        variables: &Variables,
+        next: NextParseQuery<'_>,
+    ) -> ServerResult<ExecutableDocument> {
+        let document = next.run(ctx, query, variables).await?;
+        let Some(get_request) = ctx.data_opt::<GetRequest>() else {
+            return Ok(document);
+        };
+
+        let operation = match &get_request.operation_name {
+            Some(operation_name) => document.operations.iter().find(|(name, _)| name.map(|name| name.as_str()) == Some(operation_name.as_str())),
+            None => document.operations.iter().next(),
+        };
+        // an unknown operation name is reported by the executor
+        if let Some((_, operation)) = operation {
+            if operation.node.ty != OperationType::Query {
+                let mut err = ServerError::new("Only query operations are allowed over GET.", None);
+                err.extensions.get_or_insert_with(Default::default).set("code", CODE_METHOD_NOT_ALLOWED);
+                return Err(err);
+            }
+        }
+        Ok(document)
+    }
+}
+
+/**
+Builds the HTTP response of a GET request. The ETag is the hash of the serialized body, and a matching
+`If-None-Match` gets `304 Not Modified`. Responses to authenticated requests are only privately cacheable.
+*/
+pub fn respond(req: &HttpRequest, res: Response, authenticated: bool) -> Result<HttpResponse> {
+    let body = serde_json::to_vec(&res)?;
+
+    let method_not_allowed = res.errors.iter().any(|err| {
+        err.extensions.as_ref().and_then(|extensions| extensions.get("code")) == Some(&Value::from(CODE_METHOD_NOT_ALLOWED))
+    });
+    if method_not_allowed {
+        return Ok(HttpResponse::MethodNotAllowed()
+            .insert_header((header::ALLOW, "POST"))
+            .insert_header((header::VARY, VARY))
+            .content_type(CONTENT_TYPE)
+            .body(body));
+    }
+
+    let mut cache_control = res.cache_control;
+    if authenticated {
+        cache_control.public = false;
+    }
+    let cache_control = if res.is_ok() { cache_control.value() } else { None }
+        .unwrap_or_else(|| "no-cache".to_string());
+
+    let etag = EntityTag::new_strong(format!("{:x}", Sha256::digest(&body)));
+    let not_modified = match IfNoneMatch::parse(req) {
+        Ok(IfNoneMatch::Any) => true,
+        Ok(IfNoneMatch::Items(tags)) => tags.iter().any(|tag| tag.weak_eq(&etag)),
+        Err(_) => false,
+    };
+
+    let mut builder = if not_modified {
+        HttpResponse::NotModified()
+    } else {
+        HttpResponse::Ok()
+    };
+    builder
+        .insert_header((header::CACHE_CONTROL, cache_control))
+        .insert_header((header::VARY, VARY))
+        .insert_header(ETag(etag));
+    if not_modified {
+        return Ok(builder.finish());
+    }
+    Ok(builder.content_type(CONTENT_TYPE).body(body))
+}
@@ -0,0 +1,912 @@
+/*!
+The blog backend as a library: the GraphQL schema, the passkey authentication and the routes serving them,
+which the `learning_graphql` binary runs and other actix-web apps can mount.
+*/
+
+use std::{sync::{Arc, Weak}, time::{Duration, Instant}};
+use anyhow::{anyhow, Result};
+use url::Url;
+use sea_orm::prelude::*;
+use sea_orm::{
+    DatabaseConnection,
+    DatabaseTransaction,
+    TransactionTrait,
+    QueryOrder,
+    QuerySelect,
+    ActiveValue::Set,
+};
+use actix_session::{Session, SessionMiddleware};
+use actix_web::{guard, web, HttpRequest, HttpResponse, cookie, ResponseError, http::{header, StatusCode}};
+use async_graphql::{connection::Connection, extensions, Object, EmptySubscription, Executor, Schema, SchemaBuilder, Context, Data, MaybeUndefined, http::{GraphiQLSource, Credentials}};
+use async_graphql_actix_web::{GraphQLRequest, GraphQLResponse, GraphQLSubscription};
+use futures::stream::{self, BoxStream};
+use webauthn_rs::prelude::{Webauthn, WebauthnBuilder};
+
+pub mod access_log;
+mod audit;
+pub mod csrf;
+pub mod db;
+mod auth;
+pub mod session;
+pub mod entity;
+pub mod limit;
+pub mod metrics;
+pub mod persisted;
+pub mod rate_limit;
+mod http_get;
+pub mod schema_diff;
+pub mod scheduler;
+pub mod telemetry;
+mod revision;
+mod markdown;
+mod pagination;
+mod slug;
+mod search;
+mod post_filter;
+mod role_guard;
+mod token;
+
+use entity::{api_token::{self, ApiScope}, audit_log, comment, passkey, post, post_tag, tag, user, sea_orm_active_enums::{AuditEvent, CommentStatus, PostStatus, Role}};
+use role_guard::RoleGuard;
+use session::MemorySession;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("an unspecified internal error occurred: {0}")]
+    InternalError(#[from] anyhow::Error),
+    #[error("too many requests")]
+    TooManyRequests { retry_after: Duration },
+    #[error("invalid API token")]
+    InvalidToken,
+}
+
+impl ResponseError for Error {
+
+    fn status_code(&self) -> StatusCode {
+        match &self {
+            Self::InternalError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::TooManyRequests { .. } => StatusCode::TOO_MANY_REQUESTS,
+            Self::InvalidToken => StatusCode::UNAUTHORIZED,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut res = HttpResponse::build(self.status_code());
+        if let Self::TooManyRequests { retry_after } = self {
+            // whole seconds, rounded up so that a retry is not too early
+            let secs = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+            res.insert_header((header::RETRY_AFTER, secs.to_string()));
+        }
+        if let Self::InvalidToken = self {
+            res.insert_header((header::WWW_AUTHENTICATE, "Bearer"));
+        }
+        res.body(self.to_string())
+    }
+
+}
+
+// cost of a list field, multiplied by the complexity of its selection
+const LIST_COMPLEXITY: usize = 10;
+
+/**
+The queries of the schema.
+*/
+#[derive(Debug)]
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    async fn hello(&self) -> &'static str {
+        "Hello, graphql!"
+    }
+
+    #[graphql(complexity = "pagination::complexity(first, child_complexity)", cache_control(max_age = 60))]
+    async fn posts(
+        &self,
+        ctx: &Context<'_>,
+        filter: Option<post_filter::PostFilter>,
+        order: Option<post_filter::PostOrder>,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> Result<Connection<usize, post::Model>> {
+        let page = pagination::Page::new(first, after)?;
+        let trx = trx_from_ctx(ctx)?;
+
+        let posts = post::Entity::find()
+            .filter(post::visible_to(ctx.data_opt::<user::Model>()))
+            .filter(filter.unwrap_or_default().condition()?);
+        let posts = order.unwrap_or_default().apply(posts)
+            .offset(page.offset)
+            .limit(page.fetch_limit())
+            .all(trx.as_ref()).await?;
+        Ok(page.connection(posts))
+    }
+
+    /// The post with this slug. Former slugs of a post still find it, and report its current slug.
+    async fn post(&self, ctx: &Context<'_>, slug: String) -> Result<Option<slug::PostBySlug>> {
+        let trx = trx_from_ctx(ctx)?;
+        slug::find_post(&trx, ctx.data_opt::<user::Model>(), &slug).await
+    }
+
+    /// Posts with the tag of this slug, which is normalized like tag names
+    #[graphql(complexity = "LIST_COMPLEXITY * child_complexity")]
+    async fn posts_by_tag(&self, ctx: &Context<'_>, slug: String) -> Result<Vec<post::Model>> {
+        let trx = trx_from_ctx(ctx)?;
+        let slug = slug::normalize_tag(&slug)?;
+
+        let Some(tag) = tag::Entity::find().filter(tag::Column::Slug.eq(slug)).one(trx.as_ref()).await? else {
+            return Ok(Vec::new());
+        };
+        let posts = tag.find_related(post::Entity)
+            .filter(post::visible_to(ctx.data_opt::<user::Model>()))
+            .all(trx.as_ref()).await?;
+        Ok(posts)
+    }
+
+    /// Posts matching a full-text query, best match first. `"Quoted words"` match as a phrase and `word*` as a prefix.
+    #[graphql(complexity = "pagination::complexity(first, child_complexity)")]
+    async fn search_posts(&self, ctx: &Context<'_>, query: String, first: Option<i32>, after: Option<String>) -> Result<Connection<usize, search::SearchResult>> {
+        let page = pagination::Page::new(first, after)?;
+        let trx = trx_from_ctx(ctx)?;
+        search::search_posts(&trx, ctx.data_opt::<user::Model>(), &query, page).await
+    }
+
+    /// All tags, ordered by slug
+    #[graphql(complexity = "pagination::complexity(first, child_complexity)")]
+    async fn tags(&self, ctx: &Context<'_>, first: Option<i32>, after: Option<String>) -> Result<Connection<usize, tag::Model>> {
+        let page = pagination::Page::new(first, after)?;
+        let trx = trx_from_ctx(ctx)?;
+
+        let tags = tag::Entity::find()
+            .order_by_asc(tag::Column::Slug)
+            .offset(page.offset)
+            .limit(page.fetch_limit())
+            .all(trx.as_ref()).await?;
+        Ok(page.connection(tags))
+    }
+
+    /// The audit log, newest first, optionally of one kind of event only
+    #[graphql(complexity = "pagination::complexity(first, child_complexity)", guard = "RoleGuard::new(Role::Admin)")]
+    async fn audit_log(&self, ctx: &Context<'_>, event: Option<AuditEvent>, first: Option<i32>, after: Option<String>) -> Result<Connection<usize, audit_log::Model>> {
+        let trx = trx_from_ctx(ctx)?;
+        let page = pagination::Page::new(first, after)?;
+
+        let mut query = audit_log::Entity::find();
+        if let Some(event) = event {
+            query = query.filter(audit_log::Column::Event.eq(event));
+        }
+        let entries = query
+            .order_by_desc(audit_log::Column::CreatedAt)
+            .order_by_desc(audit_log::Column::Id)
+            .offset(page.offset)
+            .limit(page.fetch_limit())
+            .all(trx.as_ref()).await?;
+        Ok(page.connection(entries))
+    }
+
+    #[graphql(complexity = "LIST_COMPLEXITY * child_complexity", guard = "RoleGuard::new(Role::Admin)")]
+    async fn users(&self, ctx: &Context<'_>) -> Result<Vec<user::Model>> {
+        let trx = trx_from_ctx(ctx)?;
+
+        let users = user::Entity::find().all(trx.as_ref()).await?;
+        Ok(users)
+    }
+}
+
+/**
+The mutations of the schema.
+*/
+#[derive(Debug)]
+pub struct Mutation;
+
+#[Object]
+impl Mutation {
+    #[graphql(guard = "RoleGuard::new(Role::Author)")]
+    async fn create_post(&self, ctx: &Context<'_>, title: String, content: String, slug: Option<String>) -> Result<post::Model> {
+        let Some(user) = ctx.data_opt::<user::Model>() else {
+            return Err(anyhow!("unauthenticated"));
+        };
+        let trx = trx_from_ctx(ctx)?;
+        let id = Uuid::new_v4();
+        let slug = match slug {
+            Some(slug) => slug::check_post_slug(&trx, id, &slug).await?,
+            None => slug::generate_post_slug(&trx, id, &title).await?,
+        };
+        let now = chrono::Utc::now().naive_utc();
+        let post = post::ActiveModel {
+            id: Set(id),
+            user_id: Set(user.id),
+            slug: Set(Some(slug)),
+            title: Set(title),
+            content: Set(content),
+            created_at: Set(now),
+            updated_at: Set(now),
+            status: Set(PostStatus::Draft),
+            published_at: Set(None),
+        };
+        let post = post.insert(trx.as_ref()).await?;
+        revision::record(&trx, &post, user.id).await?;
+        Ok(post)
+    }
+
+    #[graphql(guard = "RoleGuard::new(Role::Author)")]
+    async fn update_post(&self, ctx: &Context<'_>, id: String, title: Option<String>, content: Option<String>, slug: MaybeUndefined<String>) -> Result<post::Model> {
+        let trx = trx_from_ctx(ctx)?;
+        let post = find_own_post(ctx, &trx, &id).await?;
+        revision::ensure_baseline(&trx, &post).await?;
+
+        let slug = match slug {
+            MaybeUndefined::Undefined => post.slug.clone(),
+            MaybeUndefined::Null => None,
+            MaybeUndefined::Value(slug) => Some(slug::check_post_slug(&trx, post.id, &slug).await?),
+        };
+        let old_slug = post.slug.clone();
+        let mut post: post::ActiveModel = post.into();
+        post.slug = Set(slug);
+        if let Some(title) = title {
+            post.title = Set(title);
+        }
+        if let Some(content) = content {
+            post.content = Set(content);
+        }
+        post.updated_at = Set(chrono::Utc::now().naive_utc());
+        save_post_revision(ctx, &trx, old_slug, post).await
+    }
+
+    #[graphql(guard = "RoleGuard::new(Role::Author)")]
+    async fn restore_post_revision(&self, ctx: &Context<'_>, post_id: String, revision_id: String) -> Result<post::Model> {
+        let trx = trx_from_ctx(ctx)?;
+        let post = find_own_post(ctx, &trx, &post_id).await?;
+        revision::ensure_baseline(&trx, &post).await?;
+        let revision = revision::find(&trx, post.id, &revision_id).await?;
+        let slug = match revision.slug {
+            Some(slug) => Some(slug::check_post_slug(&trx, post.id, &slug).await?),
+            None => None,
+        };
+
+        let old_slug = post.slug.clone();
+        let mut post: post::ActiveModel = post.into();
+        post.slug = Set(slug);
+        post.title = Set(revision.title);
+        post.content = Set(revision.content);
+        post.updated_at = Set(chrono::Utc::now().naive_utc());
+        save_post_revision(ctx, &trx, old_slug, post).await
+    }
+
+    /// Replaces the tags of a post, creating the tags which don't exist yet
+    #[graphql(guard = "RoleGuard::new(Role::Author)")]
+    async fn set_post_tags(&self, ctx: &Context<'_>, post_id: String, tags: Vec<String>) -> Result<post::Model> {
+        let trx = trx_from_ctx(ctx)?;
+        let post = find_own_post(ctx, &trx, &post_id).await?;
+
+        let mut tag_ids = Vec::new();
+        for name in tags {
+            let tag = find_or_create_tag(&trx, &name).await?;
+            if !tag_ids.contains(&tag.id) {
+                tag_ids.push(tag.id);
+            }
+        }
+
+        post_tag::Entity::delete_many()
+            .filter(post_tag::Column::PostId.eq(post.id))
+            .exec(trx.as_ref()).await?;
+        if !tag_ids.is_empty() {
+            let post_tags = tag_ids.into_iter().map(|tag_id| post_tag::ActiveModel {
+                post_id: Set(post.id),
+                tag_id: Set(tag_id),
+            });
+            post_tag::Entity::insert_many(post_tags).exec_without_returning(trx.as_ref()).await?;
+        }
+        Ok(post)
+    }
+
+    /// Publishes a post now. Publishing a published post again keeps its `publishedAt`.
+    #[graphql(guard = "RoleGuard::new(Role::Author)")]
+    async fn publish_post(&self, ctx: &Context<'_>, id: String) -> Result<post::Model> {
+        let now = chrono::Utc::now().naive_utc();
+        set_post_status(ctx, &id, PostStatus::Published, |post| match post.status {
+            PostStatus::Published => post.published_at.or(Some(now)),
+            _ => Some(now),
+        }).await
+    }
+
+    /// Turns a post back into a draft and clears its `publishedAt`, so publishing it again sets a new one
+    #[graphql(guard = "RoleGuard::new(Role::Author)")]
+    async fn unpublish_post(&self, ctx: &Context<'_>, id: String) -> Result<post::Model> {
+        set_post_status(ctx, &id, PostStatus::Draft, |_| None).await
+    }
+
+    /// Publishes a post at `at`, which is its `publishedAt` until then
+    #[graphql(guard = "RoleGuard::new(Role::Author)")]
+    async fn schedule_post(&self, ctx: &Context<'_>, id: String, at: chrono::NaiveDateTime) -> Result<post::Model> {
+        if at <= chrono::Utc::now().naive_utc() {
+            return Err(anyhow!("scheduled time must be in the future"));
+        }
+        set_post_status(ctx, &id, PostStatus::Scheduled, |_| Some(at)).await
+    }
+
+    /// Archives a post. A published post keeps its `publishedAt`, a scheduled one loses it as it is never published.
+    #[graphql(guard = "RoleGuard::new(Role::Author)")]
+    async fn archive_post(&self, ctx: &Context<'_>, id: String) -> Result<post::Model> {
+        set_post_status(ctx, &id, PostStatus::Archived, |post| match post.status {
+            PostStatus::Published | PostStatus::Archived => post.published_at,
+            _ => None,
+        }).await
+    }
+
+    /// Deletes a post with its comments, tags and revisions. Admins can delete any post, authors their own.
+    // admins include the author role, so that they can delete any post
+    #[graphql(guard = "RoleGuard::new(Role::Author)")]
+    async fn delete_post(&self, ctx: &Context<'_>, id: String) -> Result<post::Model> {
+        let Some(user) = ctx.data_opt::<user::Model>() else {
+            return Err(anyhow!("unauthenticated"));
+        };
+        let trx = trx_from_ctx(ctx)?;
+        let id = Uuid::parse_str(&id)?;
+        let Some(post) = post::Entity::find_by_id(id).one(trx.as_ref()).await? else {
+            return Err(anyhow!("post not found"));
+        };
+        if post.user_id != user.id && !user.role.includes(Role::Admin) {
+            return Err(anyhow!("forbidden"));
+        }
+        post::Entity::delete_by_id(post.id).exec(trx.as_ref()).await?;
+        audit::record(trx.as_ref(), AuditEvent::PostDeleted, Some(user.id), Some(post.id), Some(post.title.clone()), audit::client_ip_of_ctx(ctx)).await?;
+        Ok(post)
+    }
+
+    /// Comments on a post the current user can read, optionally as a reply to another comment on it
+    async fn add_comment(&self, ctx: &Context<'_>, post_id: String, body: String, parent_id: Option<String>) -> Result<comment::Model> {
+        let Some(user) = ctx.data_opt::<user::Model>() else {
+            return Err(anyhow!("unauthenticated"));
+        };
+        let trx = trx_from_ctx(ctx)?;
+        let post_id = Uuid::parse_str(&post_id)?;
+        let Some(post) = post::Entity::find_by_id(post_id).filter(post::visible_to(Some(user))).one(trx.as_ref()).await? else {
+            return Err(anyhow!("post not found"));
+        };
+        let parent_id = match parent_id {
+            None => None,
+            Some(parent_id) => {
+                let parent = find_comment(&trx, &parent_id).await?;
+                if parent.post_id != post.id {
+                    return Err(anyhow!("comment not found"));
+                }
+                Some(parent.id)
+            },
+        };
+
+        let now = chrono::Utc::now().naive_utc();
+        let comment = comment::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            post_id: Set(post.id),
+            user_id: Set(user.id),
+            parent_id: Set(parent_id),
+            body: Set(comment_body(body)?),
+            status: Set(CommentStatus::Visible),
+            created_at: Set(now),
+            updated_at: Set(now),
+        };
+        let comment = comment.insert(trx.as_ref()).await?;
+        Ok(comment)
+    }
+
+    async fn edit_comment(&self, ctx: &Context<'_>, id: String, body: String) -> Result<comment::Model> {
+        let trx = trx_from_ctx(ctx)?;
+        let comment = find_own_comment(ctx, &trx, &id).await?;
+        let mut comment: comment::ActiveModel = comment.into();
+        comment.body = Set(comment_body(body)?);
+        comment.updated_at = Set(chrono::Utc::now().naive_utc());
+        let comment = comment.update(trx.as_ref()).await?;
+        Ok(comment)
+    }
+
+    /// Deletes the text of a comment, the comment itself stays in the thread so that its replies keep their place
+    async fn delete_comment(&self, ctx: &Context<'_>, id: String) -> Result<comment::Model> {
+        let trx = trx_from_ctx(ctx)?;
+        let comment = find_own_comment(ctx, &trx, &id).await?;
+        let mut comment: comment::ActiveModel = comment.into();
+        comment.body = Set(String::new());
+        comment.status = Set(CommentStatus::Deleted);
+        comment.updated_at = Set(chrono::Utc::now().naive_utc());
+        let comment = comment.update(trx.as_ref()).await?;
+        Ok(comment)
+    }
+
+    /// Hides a comment from other readers. Admins can hide any comment, authors those on their posts.
+    async fn hide_comment(&self, ctx: &Context<'_>, id: String) -> Result<comment::Model> {
+        moderate_comment(ctx, &id, CommentStatus::Hidden).await
+    }
+
+    async fn unhide_comment(&self, ctx: &Context<'_>, id: String) -> Result<comment::Model> {
+        moderate_comment(ctx, &id, CommentStatus::Visible).await
+    }
+
+    #[graphql(guard = "RoleGuard::new(Role::Admin)")]
+    async fn set_user_role(&self, ctx: &Context<'_>, user_id: String, role: Role) -> Result<user::Model> {
+        let trx = trx_from_ctx(ctx)?;
+        let user_id = Uuid::parse_str(&user_id)?;
+        let Some(user) = user::Entity::find_by_id(user_id).one(trx.as_ref()).await? else {
+            return Err(anyhow!("user not found"));
+        };
+        let old_role = user.role;
+        let mut user: user::ActiveModel = user.into();
+        user.role = Set(role);
+        let user = user.update(trx.as_ref()).await?;
+
+        let admin = ctx.data::<user::Model>().map_err(|err| anyhow!("{:?}", err))?;
+        let detail = format!("{} -> {}", old_role.to_value(), role.to_value());
+        audit::record(trx.as_ref(), AuditEvent::RoleChanged, Some(admin.id), Some(user.id), Some(detail), audit::client_ip_of_ctx(ctx)).await?;
+        Ok(user)
+    }
+
+    /// Revokes the passkey of a user, for a lost or compromised authenticator. The sessions of the user end
+    /// and their API tokens are revoked too, so that nothing logs the user in any longer.
+    #[graphql(guard = "RoleGuard::new(Role::Admin)")]
+    async fn revoke_passkey(&self, ctx: &Context<'_>, user_id: String) -> Result<user::PasskeyInfo> {
+        let trx = trx_from_ctx(ctx)?;
+        let user_id = Uuid::parse_str(&user_id)?;
+        let Some(passkey) = passkey::Entity::find_by_id(user_id).one(trx.as_ref()).await? else {
+            return Err(anyhow!("passkey not found"));
+        };
+        passkey::Entity::delete_by_id(user_id).exec(trx.as_ref()).await?;
+
+        let passkey = user::PasskeyInfo::try_from(passkey)?;
+        let admin = ctx.data::<user::Model>().map_err(|err| anyhow!("{:?}", err))?;
+        let ip = audit::client_ip_of_ctx(ctx);
+        audit::record(trx.as_ref(), AuditEvent::PasskeyRevoked, Some(admin.id), Some(user_id), Some(passkey.credential_id.clone()), ip.clone()).await?;
+        let api_tokens = api_token::Entity::find().filter(api_token::Column::UserId.eq(user_id)).all(trx.as_ref()).await?;
+        for api_token in api_tokens {
+            api_token::Entity::delete_by_id(api_token.id).exec(trx.as_ref()).await?;
+            audit::record(trx.as_ref(), AuditEvent::ApiTokenRevoked, Some(admin.id), Some(user_id), Some(api_token.name), ip.clone()).await?;
+        }
+        // the sessions are not in the database, a rollback after this point leaves them ended
+        session::end_sessions_of_user(user_id);
+        Ok(passkey)
+    }
+
+    /// Creates an API token of the current user for clients which cannot log in with a passkey, sent as
+    /// `Authorization: Bearer <token>`. The token is only returned here. It may call the mutations of its scopes only,
+    /// and never those of the tokens themselves.
+    async fn create_api_token(&self, ctx: &Context<'_>, name: String, scopes: Vec<ApiScope>, expires_at: Option<chrono::NaiveDateTime>) -> Result<token::CreatedApiToken> {
+        let Some(user) = ctx.data_opt::<user::Model>() else {
+            return Err(anyhow!("unauthenticated"));
+        };
+        let trx = trx_from_ctx(ctx)?;
+        let created = token::create(trx.as_ref(), user.id, name, scopes, expires_at).await?;
+        let detail = created.api_token.name.clone();
+        audit::record(trx.as_ref(), AuditEvent::ApiTokenCreated, Some(user.id), Some(user.id), Some(detail), audit::client_ip_of_ctx(ctx)).await?;
+        Ok(created)
+    }
+
+    async fn revoke_api_token(&self, ctx: &Context<'_>, id: String) -> Result<api_token::Model> {
+        let Some(user) = ctx.data_opt::<user::Model>() else {
+            return Err(anyhow!("unauthenticated"));
+        };
+        let trx = trx_from_ctx(ctx)?;
+        let id = Uuid::parse_str(&id)?;
+        let Some(api_token) = api_token::Entity::find_by_id(id).one(trx.as_ref()).await? else {
+            return Err(anyhow!("token not found"));
+        };
+        if api_token.user_id != user.id {
+            return Err(anyhow!("token not found"));
+        }
+        api_token::Entity::delete_by_id(api_token.id).exec(trx.as_ref()).await?;
+        audit::record(trx.as_ref(), AuditEvent::ApiTokenRevoked, Some(user.id), Some(user.id), Some(api_token.name.clone()), audit::client_ip_of_ctx(ctx)).await?;
+        Ok(api_token)
+    }
+}
+
+// sets the status of a post, and its `published_at` to what `published_at` makes of the post before the change
+async fn set_post_status(
+    ctx: &Context<'_>,
+    id: &str,
+    status: PostStatus,
+    published_at: impl FnOnce(&post::Model) -> Option<chrono::NaiveDateTime>,
+) -> Result<post::Model> {
+    let trx = trx_from_ctx(ctx)?;
+    let post = find_own_post(ctx, &trx, id).await?;
+    let published_at = published_at(&post);
+    let mut post: post::ActiveModel = post.into();
+    post.status = Set(status);
+    post.published_at = Set(published_at);
+    let post = post.update(trx.as_ref()).await?;
+    Ok(post)
+}
+
+// loads the tag with the slug of `name`, or creates it with `name` as its display name
+async fn find_or_create_tag(trx: &DatabaseTransaction, name: &str) -> Result<tag::Model> {
+    let slug = slug::normalize_tag(name)?;
+    if let Some(tag) = tag::Entity::find().filter(tag::Column::Slug.eq(&slug)).one(trx).await? {
+        return Ok(tag);
+    }
+    let tag = tag::ActiveModel {
+        id: Set(Uuid::new_v4()),
+        slug: Set(slug),
+        name: Set(name.trim().to_string()),
+        created_at: Set(chrono::Utc::now().naive_utc()),
+    };
+    let tag = tag.insert(trx).await?;
+    Ok(tag)
+}
+
+// saves an edit of a post and records the result as a new revision, and the slug before it in the slug history
+async fn save_post_revision(ctx: &Context<'_>, trx: &DatabaseTransaction, old_slug: Option<String>, post: post::ActiveModel) -> Result<post::Model> {
+    let user = ctx.data::<user::Model>().map_err(|_| anyhow!("unauthenticated"))?;
+    let post = post.update(trx).await?;
+    revision::record(trx, &post, user.id).await?;
+    slug::record_change(trx, post.id, old_slug.as_deref(), post.slug.as_deref()).await?;
+    Ok(post)
+}
+
+// loads a post the current user is the author of
+async fn find_own_post(ctx: &Context<'_>, trx: &DatabaseTransaction, id: &str) -> Result<post::Model> {
+    let Some(user) = ctx.data_opt::<user::Model>() else {
+        return Err(anyhow!("unauthenticated"));
+    };
+    let id = Uuid::parse_str(id)?;
+    let Some(post) = post::Entity::find_by_id(id).one(trx).await? else {
+        return Err(anyhow!("post not found"));
+    };
+    if post.user_id != user.id {
+        return Err(anyhow!("forbidden"));
+    }
+    Ok(post)
+}
+
+// loads a comment which is not deleted
+async fn find_comment(trx: &DatabaseTransaction, id: &str) -> Result<comment::Model> {
+    let id = Uuid::parse_str(id)?;
+    match comment::Entity::find_by_id(id).one(trx).await? {
+        Some(comment) if comment.status != CommentStatus::Deleted => Ok(comment),
+        _ => Err(anyhow!("comment not found")),
+    }
+}
+
+// loads a comment the current user is the author of
+async fn find_own_comment(ctx: &Context<'_>, trx: &DatabaseTransaction, id: &str) -> Result<comment::Model> {
+    let Some(user) = ctx.data_opt::<user::Model>() else {
+        return Err(anyhow!("unauthenticated"));
+    };
+    let comment = find_comment(trx, id).await?;
+    if comment.user_id != user.id {
+        return Err(anyhow!("forbidden"));
+    }
+    Ok(comment)
+}
+
+// sets the status of a comment, as an admin or the author of the post
+async fn moderate_comment(ctx: &Context<'_>, id: &str, status: CommentStatus) -> Result<comment::Model> {
+    let Some(user) = ctx.data_opt::<user::Model>() else {
+        return Err(anyhow!("unauthenticated"));
+    };
+    let trx = trx_from_ctx(ctx)?;
+    let comment = find_comment(&trx, id).await?;
+    if !user.role.includes(Role::Admin) {
+        let post = post::Entity::find_by_id(comment.post_id).one(trx.as_ref()).await?;
+        if post.map(|post| post.user_id) != Some(user.id) {
+            return Err(anyhow!("forbidden"));
+        }
+    }
+    let mut comment: comment::ActiveModel = comment.into();
+    comment.status = Set(status);
+    let comment = comment.update(trx.as_ref()).await?;
+    Ok(comment)
+}
+
+fn comment_body(body: String) -> Result<String> {
+    if body.trim().is_empty() {
+        return Err(anyhow!("comment must not be empty"));
+    }
+    Ok(body)
+}
+
+fn trx_from_ctx(ctx: &Context<'_>) -> Result<Arc<DatabaseTransaction>> {
+    ctx.data::<Weak<DatabaseTransaction>>().map_err(|err| anyhow!("no transaction: {:?}", err))?
+        .upgrade().ok_or_else(|| anyhow!("transaction is already dropped"))
+}
+
+// compares secrets without an early exit, so the time taken does not tell how much of a guess was right
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+
+/**
+Everything the app is built from besides the database.
+*/
+#[derive(Clone)]
+pub struct AppConfig {
+    pub rp_id: String,
+    pub rp_origin: Url,
+    pub limits: limit::QueryLimits,
+    pub persisted_queries: persisted::PersistedQueryConfig,
+    pub trusted_documents: Option<persisted::TrustedDocuments>,
+    pub graphiql: bool,
+    pub introspection: bool,
+    pub metrics: metrics::MetricsConfig,
+    pub rate_limits: rate_limit::RateLimitConfig,
+    pub csrf: csrf::CsrfConfig,
+}
+
+/**
+What the routes of [configure] serve from, registered as `web::Data<AppState>` by the embedding app.
+Build it once and clone the `web::Data` into every worker, as the schema and its caches are meant to be shared.
+*/
+pub struct AppState {
+    pub schema: AppSchema,
+    pub webauthn: Webauthn,
+    pub conn: DatabaseConnection,
+    pub graphiql: bool,
+    pub metrics: metrics::Metrics,
+    pub metrics_token: Option<String>,
+    pub auth_limiter: rate_limit::AuthLimiter,
+    /// Serialized origins whose pages may send requests with the session cookie, the relying party's first
+    pub allowed_origins: Vec<String>,
+}
+
+impl AppState {
+    pub fn new(config: &AppConfig, conn: DatabaseConnection) -> Result<Self> {
+        let webauthn = WebauthnBuilder::new(&config.rp_id, &config.rp_origin)?.build()?;
+        let metrics = metrics::Metrics::new()?;
+        let schema = build_schema(config, &conn, &metrics);
+        let allowed_origins = std::iter::once(&config.rp_origin)
+            .chain(&config.csrf.allowed_origins)
+            .map(|origin| origin.origin().ascii_serialization())
+            .collect();
+        Ok(Self {
+            schema,
+            webauthn,
+            conn,
+            graphiql: config.graphiql,
+            metrics,
+            // an empty token, as from `METRICS_TOKEN=`, would let `Bearer ` through
+            metrics_token: config.metrics.metrics_token.clone().filter(|token| !token.trim().is_empty()),
+            auth_limiter: rate_limit::AuthLimiter::new(&config.rate_limits),
+            allowed_origins,
+        })
+    }
+}
+
+/**
+Registers the auth and GraphQL routes, which need an [AppState] in the app data and the [session_middleware] around them.
+
+```ignore
+let state = web::Data::new(AppState::new(&config, conn)?);
+let key = cookie::Key::generate();
+HttpServer::new(move || {
+    App::new()
+        .app_data(state.clone())
+        .wrap(learning_graphql::access_log::AccessLog::stdout())
+        .wrap(learning_graphql::session_middleware(key.clone()))
+        .wrap(learning_graphql::metrics::RequestMetrics)
+        .wrap(learning_graphql::telemetry::RequestTracing::new())
+        .configure(learning_graphql::configure)
+})
+```
+*/
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg
+        .service(
+            web::scope("/auth")
+                .wrap(csrf::CsrfProtection)
+                .service(web::resource("/csrf").guard(guard::Get()).to(csrf::issue_token))
+                .service(web::resource("/register/start").guard(guard::Post()).to(auth::start_registration))
+                .service(web::resource("/register/finish").guard(guard::Post()).to(auth::finish_registration))
+                .service(web::resource("/auth/start").guard(guard::Post()).to(auth::start_authentication))
+                .service(web::resource("/auth/finish").guard(guard::Post()).to(auth::finish_authentication))
+        )
+        .service(
+            web::resource("/graphql")
+                .wrap(csrf::CsrfProtection)
+                .route(web::post().to(handle_graphql))
+                .route(web::get().to(handle_graphql_get))
+        )
+        .service(web::resource("/graphql/ws").guard(guard::Get()).to(handle_graphql_ws))
+        .service(web::resource("/graphiql").guard(guard::Get()).to(graphiql_source))
+        .service(web::resource("/metrics").guard(guard::Get()).to(metrics::scrape_with_token));
+}
+
+/**
+Registers `/metrics` without the token check, for an app listening on the separate metrics address only.
+*/
+pub fn configure_metrics(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/metrics").guard(guard::Get()).to(metrics::scrape));
+}
+
+/**
+The session middleware the routes of [configure] expect, with sessions in memory and their cookies signed by `key`,
+which must be the same for all workers.
+*/
+pub fn session_middleware(key: cookie::Key) -> SessionMiddleware<MemorySession> {
+    SessionMiddleware::builder(MemorySession, key)
+        .cookie_name("sess_id".to_string())
+        .cookie_http_only(true)
+        .cookie_secure(false)
+        // not sent along with requests from other sites, on top of the checks of [csrf::CsrfProtection]
+        .cookie_same_site(cookie::SameSite::Strict)
+        .build()
+}
+
+async fn graphiql_source(state: web::Data<AppState>) -> HttpResponse {
+    if !state.graphiql {
+        return HttpResponse::NotFound().finish();
+    }
+    let source = GraphiQLSource::build()
+        .endpoint("/graphql")
+        .subscription_endpoint("/graphql/ws")
+        // the session cookie authenticates the user
+        .credentials(Credentials::SameOrigin)
+        .finish();
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(source)
+}
+
+pub type AppSchema = Schema<QueryRoot, Mutation, EmptySubscription>;
+
+/**
+The schema without limits and extensions, shared by the server and `schema print`, so the exported SDL is the served one.
+*/
+pub fn schema_builder() -> SchemaBuilder<QueryRoot, Mutation, EmptySubscription> {
+    Schema::build(QueryRoot, Mutation, EmptySubscription)
+}
+
+/**
+The served schema, with the limits and extensions of `config`, counting its operations in `metrics`.
+*/
+pub fn build_schema(config: &AppConfig, conn: &DatabaseConnection, metrics: &metrics::Metrics) -> AppSchema {
+    let schema = schema_builder()
+        .limit_depth(config.limits.limit_depth)
+        .limit_complexity(config.limits.limit_complexity)
+        .limit_recursive_depth(config.limits.limit_recursive_depth)
+        .extension(extensions::Tracing)
+        .extension(limit::QueryLimitCodes::new(&config.limits))
+        .extension(http_get::QueryOnlyOverGet)
+        .extension(token::ScopeCheck)
+        .extension(metrics::GraphQLMetrics::new(metrics.clone()))
+        .extension(access_log::OperationNames);
+    let schema = persisted::register(schema, &config.persisted_queries, config.trusted_documents.as_ref(), conn);
+    let schema = if config.introspection { schema } else { schema.disable_introspection() };
+    schema.finish()
+}
+
+// the user a GraphQL request is made by, with the scopes of the API token when authenticated by one
+struct Viewer {
+    user: user::Model,
+    scopes: Option<token::TokenScopes>,
+}
+
+// authenticates a request by the bearer token of its Authorization header, or else by its session.
+// A request with a token which is unknown or expired is rejected rather than served anonymously.
+async fn viewer(http_req: &HttpRequest, session: &Session, state: &AppState) -> Result<Option<Viewer>, Error> {
+    let Some(authorization) = http_req.headers().get(header::AUTHORIZATION) else {
+        let user = auth::session_user(session, &state.conn).await?;
+        return Ok(user.map(|user| Viewer { user, scopes: None }));
+    };
+    let Some(bearer) = authorization.to_str().ok().and_then(|value| value.strip_prefix("Bearer ")) else {
+        return Err(Error::InvalidToken);
+    };
+    match token::authenticate(&state.conn, bearer).await? {
+        Some((user, scopes)) => Ok(Some(Viewer { user, scopes: Some(scopes) })),
+        None => Err(Error::InvalidToken),
+    }
+}
+
+async fn handle_graphql(http_req: HttpRequest, session: Session, state: web::Data<AppState>, req: GraphQLRequest) -> Result<GraphQLResponse, Error> {
+    let viewer = viewer(&http_req, &session, &state).await?;
+    let res = handle_graphql_anyhow_result(&http_req, viewer, state, req.into_inner()).await?;
+    Ok(res.into())
+}
+
+async fn handle_graphql_ws(http_req: HttpRequest, payload: web::Payload, session: Session, state: web::Data<AppState>) -> Result<HttpResponse, actix_web::Error> {
+    // browsers do not apply the same-origin policy to WebSockets, so a page of any origin could open one with the cookie
+    if !csrf::origin_allowed(&http_req, &state.allowed_origins) {
+        return Err(actix_web::error::ErrorForbidden("cross-origin request"));
+    }
+    let user = auth::session_user(&session, &state.conn).await.map_err(Error::from)?;
+    if let Some(user) = &user {
+        telemetry::record_user(user.id);
+    }
+    let executor = WsExecutor { state: state.clone(), user_id: user.map(|user| user.id), client_ip: audit::client_ip(&http_req) };
+    GraphQLSubscription::new(executor).start(&http_req, payload)
+}
+
+// executes the operations of a WebSocket connection like those of HTTP requests, each in a transaction of its own
+#[derive(Clone)]
+struct WsExecutor {
+    state: web::Data<AppState>,
+    // the user of the session at the handshake, who is reloaded for every operation like for every HTTP request
+    user_id: Option<Uuid>,
+    client_ip: Option<String>,
+}
+
+impl WsExecutor {
+    // the current state of the user, none once they are deleted or logged out of every session
+    async fn user(&self) -> Result<Option<user::Model>> {
+        let Some(user_id) = self.user_id else {
+            return Ok(None);
+        };
+        if session::valid_until_of_user(user_id).is_empty() {
+            return Ok(None);
+        }
+        let user = user::Entity::find_by_id(user_id).one(&self.state.conn).await?;
+        Ok(user)
+    }
+
+    async fn execute_anyhow_result(&self, req: async_graphql::Request) -> Result<async_graphql::Response> {
+        let mut req = req.data(audit::ClientIp(self.client_ip.clone()));
+        if let Some(user) = self.user().await? {
+            req = req.data(user);
+        }
+        execute_in_transaction(&self.state, req).await
+    }
+}
+
+impl Executor for WsExecutor {
+    async fn execute(&self, req: async_graphql::Request) -> async_graphql::Response {
+        self.execute_anyhow_result(req).await.unwrap_or_else(|err| {
+            async_graphql::Response::from_errors(vec![async_graphql::ServerError::new(err.to_string(), None)])
+        })
+    }
+
+    // the schema has no subscriptions, so every operation has a single response
+    fn execute_stream(&self, req: async_graphql::Request, _session_data: Option<Arc<Data>>) -> BoxStream<'static, async_graphql::Response> {
+        let executor = self.clone();
+        Box::pin(stream::once(async move { executor.execute(req).await }))
+    }
+}
+
+async fn handle_graphql_get(http_req: HttpRequest, session: Session, state: web::Data<AppState>, req: GraphQLRequest) -> Result<HttpResponse, Error> {
+    let viewer = viewer(&http_req, &session, &state).await?;
+    let res = handle_graphql_get_anyhow_result(http_req, viewer, state, req).await?;
+    Ok(res)
+}
+
+async fn handle_graphql_get_anyhow_result(http_req: HttpRequest, viewer: Option<Viewer>, state: web::Data<AppState>, req: GraphQLRequest) -> Result<HttpResponse> {
+    let mut req = req.into_inner();
+    if req.operation_name.is_none() {
+        req.operation_name = http_get::operation_name(&http_req);
+    }
+    let authenticated = viewer.is_some();
+    let get_request = http_get::GetRequest { operation_name: req.operation_name.clone() };
+
+    let res = handle_graphql_anyhow_result(&http_req, viewer, state, req.data(get_request)).await?;
+    http_get::respond(&http_req, res, authenticated)
+}
+
+async fn handle_graphql_anyhow_result(http_req: &HttpRequest, viewer: Option<Viewer>, state: web::Data<AppState>, req: async_graphql::Request) -> Result<async_graphql::Response> {
+    let executed = access_log::ExecutedOperation::default();
+    let mut req = req.data(audit::ClientIp(audit::client_ip(http_req))).data(executed.clone());
+    if let Some(Viewer { user, scopes }) = viewer {
+        telemetry::record_user(user.id);
+        access_log::record_user(http_req, user.id);
+        req = req.data(user);
+        if let Some(scopes) = scopes {
+            req = req.data(scopes);
+        }
+    }
+
+    let res = execute_in_transaction(&state, req).await?;
+    access_log::record_operation(http_req, executed.name().as_deref());
+    Ok(res)
+}
+
+// executes `req` in a transaction, which is committed unless the response has errors
+async fn execute_in_transaction(state: &AppState, req: async_graphql::Request) -> Result<async_graphql::Response> {
+    let started = Instant::now();
+    let trx = state.conn.begin().await?;
+    let trx = Arc::new(trx);
+    let res = state.schema.execute(
+        req.data(Arc::downgrade(&trx)),
+    ).await;
+    let trx = Arc::try_unwrap(trx).expect("only one reference to the transaction should exist");
+    if res.is_err() {
+        let _ = trx.rollback().await;
+        state.metrics.observe_transaction("rollback", started.elapsed());
+        return Ok(res);
+    }
+    trx.commit().await?;
+    state.metrics.observe_transaction("commit", started.elapsed());
+
+    Ok(res)
+}
@@ -0,0 +1,128 @@
+// query size limits, and an extension which tags limit rejections with error codes
+
+use std::sync::Arc;
+use async_graphql::{
+    async_trait,
+    extensions::{Extension, ExtensionContext, ExtensionFactory, NextParseQuery, NextValidation},
+    parser::types::{ExecutableDocument, Selection, SelectionSet},
+    ServerError,
+    ServerResult,
+    ValidationResult,
+    Variables,
+};
+
+/**
+Limits applied to every incoming GraphQL document, configurable from the command line.
+*/
+#[derive(Debug, Clone, clap::Args)]
+pub struct QueryLimits {
+    /// Maximum nesting depth of selection sets
+    #[clap(long, default_value_t = 10)]
+    pub limit_depth: usize,
+    /// Maximum total complexity of a query, where a connection costs its page size times its selection.
+    /// The default lets a default page nest a default page with a few fields, like the comments of posts.
+    #[clap(long, default_value_t = 5000)]
+    pub limit_complexity: usize,
+    /// Maximum recursion depth the parser accepts, including fragment spreads
+    #[clap(long, default_value_t = 32)]
+    pub limit_recursive_depth: usize,
+    /// Maximum number of aliased fields in a document
+    #[clap(long, default_value_t = 30)]
+    pub limit_aliases: usize,
+}
+
+pub const CODE_TOO_DEEP: &str = "QUERY_TOO_DEEP";
+pub const CODE_TOO_COMPLEX: &str = "QUERY_TOO_COMPLEX";
+pub const CODE_RECURSION_TOO_DEEP: &str = "QUERY_RECURSION_TOO_DEEP";
+pub const CODE_TOO_MANY_ALIASES: &str = "QUERY_TOO_MANY_ALIASES";
+
+/**
+Extension factory which counts aliases and attaches a `code` to errors raised by the schema limits.
+*/
+pub struct QueryLimitCodes {
+    limit_aliases: usize,
+}
+
+impl QueryLimitCodes {
+    pub fn new(limits: &QueryLimits) -> Self {
+        Self { limit_aliases: limits.limit_aliases }
+    }
+}
+
+impl ExtensionFactory for QueryLimitCodes {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(QueryLimitCodesExtension { limit_aliases: self.limit_aliases })
+    }
+}
+
+struct QueryLimitCodesExtension {
+    limit_aliases: usize,
+}
+
+#[async_trait::async_trait]
+impl Extension for QueryLimitCodesExtension {
+    async fn parse_query(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        query: &str,
+        variables: &Variables,
+        next: NextParseQuery<'_>,
+    ) -> ServerResult<ExecutableDocument> {
+        let document = next.run(ctx, query, variables).await.map_err(with_code)?;
+        let aliases = count_aliases(&document);
+        if aliases > self.limit_aliases {
+            let err = ServerError::new(format!("Query has too many aliases: {} (limit: {})", aliases, self.limit_aliases), None);
+            return Err(set_code(err, CODE_TOO_MANY_ALIASES));
+        }
+        Ok(document)
+    }
+
+    async fn validation(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        next: NextValidation<'_>,
+    ) -> Result<ValidationResult, Vec<ServerError>> {
+        next.run(ctx).await.map_err(|errs| errs.into_iter().map(with_code).collect())
+    }
+}
+
+// async-graphql reports limit violations only by message, so map them to codes here
+fn with_code(err: ServerError) -> ServerError {
+    let code = if err.message == "Query is nested too deep." {
+        CODE_TOO_DEEP
+    } else if err.message == "Query is too complex." {
+        CODE_TOO_COMPLEX
+    } else if err.message.starts_with("The recursion depth of the query cannot be greater than") {
+        CODE_RECURSION_TOO_DEEP
+    } else {
+        return err;
+    };
+    set_code(err, code)
+}
+
+fn set_code(mut err: ServerError, code: &str) -> ServerError {
+    err.extensions.get_or_insert_with(Default::default).set("code", code);
+    err
+}
+
+fn count_aliases(document: &ExecutableDocument) -> usize {
+    fn count_in(selection_set: &SelectionSet) -> usize {
+        selection_set.items.iter().map(|selection| match &selection.node {
+            Selection::Field(field) => {
+                let alias = if field.node.alias.is_some() { 1 } else { 0 };
+                alias + count_in(&field.node.selection_set.node)
+            },
+            Selection::InlineFragment(fragment) => count_in(&fragment.node.selection_set.node),
+            Selection::FragmentSpread(_) => 0,
+        }).sum()
+    }
+
+    // fragments are counted once per definition, the recursive depth limit bounds their spread
+    let in_operations: usize = document.operations.iter()
+        .map(|(_, operation)| count_in(&operation.node.selection_set.node))
+        .sum();
+    let in_fragments: usize = document.fragments.values()
+        .map(|fragment| count_in(&fragment.node.selection_set.node))
+        .sum();
+    in_operations + in_fragments
+}
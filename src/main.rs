@@ -1,199 +1,181 @@
-use std::sync::{Arc, Weak};
-use anyhow::{anyhow, Result};
+use std::{fs, path::PathBuf, time::Duration};
+use anyhow::{bail, Result};
 use url::Url;
 use sea_orm::prelude::*;
-use sea_orm::{
-    DatabaseTransaction,
-    TransactionTrait,
-    ActiveValue::Set,
-};
+use sea_orm::{DatabaseConnection, QueryOrder, ActiveValue::Set};
 use clap::Parser;
-use actix_session::{Session, SessionMiddleware};
-use actix_web::{guard, web, App, HttpServer, HttpResponse, cookie, ResponseError, http::StatusCode};
-use async_graphql::{extensions, Object, EmptySubscription, Schema, Context, http::{playground_source, GraphQLPlaygroundConfig}};
-use async_graphql_actix_web::{GraphQLRequest, GraphQLResponse};
-use webauthn_rs::prelude::WebauthnBuilder;
-
-mod db;
-mod auth;
-mod session;
-mod entity;
-
-use entity::{post, user};
-use session::MemorySession;
-
-#[derive(thiserror::Error, Debug)]
-pub enum Error {
-    #[error("an unspecified internal error occurred: {0}")]
-    InternalError(#[from] anyhow::Error),
-}
-
-impl ResponseError for Error {
-
-    fn status_code(&self) -> StatusCode {
-        match &self {
-            Self::InternalError(_) => StatusCode::INTERNAL_SERVER_ERROR
-        }
-    }
-
-    fn error_response(&self) -> HttpResponse {
-        HttpResponse::build(self.status_code()).body(self.to_string())
-    }
-
-}
+use futures::FutureExt;
+use actix_web::{guard, web, App, HttpServer, cookie};
+
+use learning_graphql::{
+    access_log::AccessLog,
+    configure,
+    db,
+    configure_metrics,
+    csrf,
+    limit,
+    metrics,
+    persisted,
+    rate_limit,
+    scheduler,
+    schema_builder,
+    schema_diff,
+    session_middleware,
+    telemetry,
+    AppConfig,
+    AppState,
+    entity::{user, sea_orm_active_enums::Role},
+};
 
 #[derive(Debug, Parser)]
 struct Args {
+    /// SeaORM URL of the database
+    #[clap(long, global = true, default_value = db::DEFAULT_URL)]
+    database_url: String,
+    #[clap(flatten)]
+    telemetry: telemetry::TelemetryConfig,
     #[clap(subcommand)]
     subcmd: SubCommand,
 }
 
 #[derive(Debug, Parser)]
 enum SubCommand {
-    HttpServer { hostname: String, port: u16 },
+    HttpServer {
+        hostname: String,
+        port: u16,
+        #[clap(flatten)]
+        limits: limit::QueryLimits,
+        #[clap(flatten)]
+        persisted_queries: persisted::PersistedQueryConfig,
+        #[clap(flatten)]
+        metrics: metrics::MetricsConfig,
+        #[clap(flatten)]
+        rate_limits: rate_limit::RateLimitConfig,
+        #[clap(flatten)]
+        csrf: csrf::CsrfConfig,
+        /// Serve GraphiQL at /graphiql, enabled by default in debug builds only
+        #[clap(long, default_value_t = cfg!(debug_assertions), action = clap::ArgAction::Set)]
+        graphiql: bool,
+        /// Answer introspection queries, enabled by default in debug builds only
+        #[clap(long, default_value_t = cfg!(debug_assertions), action = clap::ArgAction::Set)]
+        introspection: bool,
+        /// How often scheduled posts are checked for publication, in seconds
+        #[clap(long, default_value_t = 60)]
+        publish_interval_secs: u64,
+    },
+    Schema {
+        #[clap(subcommand)]
+        subcmd: SchemaCommand,
+    },
+    /// Make the first registered user an admin, unless there is an admin already
+    BootstrapAdmin,
 }
 
-#[derive(Debug)]
-struct QueryRoot;
-
-#[Object]
-impl QueryRoot {
-    async fn hello(&self) -> &'static str {
-        "Hello, graphql!"
-    }
-
-    async fn posts(&self, ctx: &Context<'_>) -> Result<Vec<post::Model>> {
-        let trx = trx_from_ctx(ctx)?;
-
-        let posts = post::Entity::find().all(trx.as_ref()).await?;
-        Ok(posts)
-    }
-
-    async fn users(&self, ctx: &Context<'_>) -> Result<Vec<user::Model>> {
-        let trx = trx_from_ctx(ctx)?;
-
-        let users = user::Entity::find().all(trx.as_ref()).await?;
-        Ok(users)
-    }
-}
-
-#[derive(Debug)]
-struct Mutation;
-
-#[Object]
-impl Mutation {
-    async fn create_post(&self, ctx: &Context<'_>, title: String, content: String, slug: Option<String>) -> Result<post::Model> {
-        let Some(user) = ctx.data_opt::<user::Model>() else {
-            return Err(anyhow!("unauthenticated"));
-        };
-        let trx = trx_from_ctx(ctx)?;
-        let now = chrono::Utc::now().naive_utc();
-        let post = post::ActiveModel {
-            id: Set(Uuid::new_v4()),
-            user_id: Set(user.id),
-            slug: Set(slug),
-            title: Set(title),
-            content: Set(content),
-            created_at: Set(now),
-            updated_at: Set(now),
-        };
-        let post = post.insert(trx.as_ref()).await?;
-        Ok(post)
-    }
-}
-
-fn trx_from_ctx(ctx: &Context<'_>) -> Result<Arc<DatabaseTransaction>> {
-    ctx.data::<Weak<DatabaseTransaction>>().map_err(|err| anyhow!("no transaction: {:?}", err))?
-        .upgrade().ok_or_else(|| anyhow!("transaction is already dropped"))
+#[derive(Debug, Parser)]
+enum SchemaCommand {
+    /// Print the SDL of the GraphQL schema
+    Print {
+        /// Write to this file instead of stdout
+        #[clap(long)]
+        output: Option<PathBuf>,
+    },
+    /// Classify the changes between two SDL files as breaking or non-breaking
+    Diff { old: PathBuf, new: PathBuf },
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    env_logger::init();
-
     let args = Args::parse();
+    let _telemetry = telemetry::init(&args.telemetry)?;
     match args.subcmd {
-        SubCommand::HttpServer { hostname, port } => {
-            let hostname_cloned = hostname.clone();
-            HttpServer::new(move || {
-                let rp_id = &hostname_cloned;
-                let rp_origin = Url::parse(&format!("http://{}:{}", hostname_cloned, port)).expect("hostname and port must be valid");
-                let webauthn = WebauthnBuilder::new(&rp_id, &rp_origin).expect("correct webauthn origin is prerequisite").build();
-
-                let schema = Schema::build(QueryRoot, Mutation, EmptySubscription)
-                    .extension(extensions::Logger)
-                    .finish();
-
-                App::new()
-                    .wrap(
-                        // Session middleware, just memory store for now, so key is also temporary
-                        SessionMiddleware::builder(MemorySession, cookie::Key::generate())
-                            .cookie_name("sess_id".to_string())
-                            .cookie_http_only(true)
-                            .cookie_secure(false)
-                            .build()
-                    )
-                    .service(
-                        web::scope("/auth")
-                            .app_data(web::Data::new(webauthn))
-                            .service(web::resource("/register/start").guard(guard::Post()).to(auth::start_registration))
-                            .service(web::resource("/register/finish").guard(guard::Post()).to(auth::finish_registration))
-                            .service(web::resource("/auth/start").guard(guard::Post()).to(auth::start_authentication))
-                            .service(web::resource("/auth/finish").guard(guard::Post()).to(auth::finish_authentication))
-                    )
-                    .service(web::resource("/").guard(guard::Get()).to(hello))
-                    .service(
-                        web::resource("/graphql")
-                            .app_data(web::Data::new(schema))
-                            .guard(guard::Post()).to(
-                                handle_graphql)
-                    )
-                    .service(web::resource("/playground").guard(guard::Get()).to(graphql_playgound))
-            }).bind((hostname, port))?.run().await?;
+        SubCommand::HttpServer { hostname, port, limits, persisted_queries, metrics: metrics_config, rate_limits, csrf, graphiql, introspection, publish_interval_secs } => {
+            let conn = db::connect(&args.database_url).await?;
+            scheduler::spawn(conn.clone(), Duration::from_secs(publish_interval_secs));
+            let trusted_documents = match &persisted_queries.trusted_documents {
+                Some(path) => Some(persisted::TrustedDocuments::load(path)?),
+                None => None,
+            };
+            let config = AppConfig {
+                rp_id: hostname.clone(),
+                rp_origin: Url::parse(&format!("http://{}:{}", hostname, port))?,
+                limits,
+                persisted_queries,
+                trusted_documents,
+                graphiql,
+                introspection,
+                metrics: metrics_config,
+                rate_limits,
+                csrf,
+            };
+            // built once and cloned into every worker, so that each worker accepts the session cookies of the others
+            let state = web::Data::new(AppState::new(&config, conn)?);
+            // just memory store for now, so key is also temporary
+            let key = cookie::Key::generate();
+            let server = {
+                let state = state.clone();
+                HttpServer::new(move || {
+                    App::new()
+                        .app_data(state.clone())
+                        .wrap(AccessLog::stdout())
+                        .wrap(session_middleware(key.clone()))
+                        .wrap(metrics::RequestMetrics)
+                        .wrap(telemetry::RequestTracing::new())
+                        .service(web::resource("/").guard(guard::Get()).to(hello))
+                        .configure(configure)
+                }).bind((hostname, port))?.run()
+            };
+            match config.metrics.metrics_bind {
+                Some(addr) => {
+                    let metrics_server = HttpServer::new(move || App::new().app_data(state.clone()).configure(configure_metrics))
+                        .workers(1)
+                        .bind(addr)?
+                        .run();
+                    futures::try_join!(server, metrics_server)?;
+                },
+                None => server.await?,
+            }
+        },
+        SubCommand::Schema { subcmd: SchemaCommand::Print { output } } => {
+            let sdl = schema_builder().finish().sdl();
+            match output {
+                Some(path) => fs::write(path, sdl)?,
+                None => print!("{}", sdl),
+            }
+        },
+        SubCommand::Schema { subcmd: SchemaCommand::Diff { old, new } } => {
+            let changes = schema_diff::diff(&fs::read_to_string(old)?, &fs::read_to_string(new)?)?;
+            for change in &changes {
+                println!("{}", change);
+            }
+            if changes.iter().any(|change| change.severity == schema_diff::Severity::Breaking) {
+                bail!("the schema has breaking changes");
+            }
+        },
+        SubCommand::BootstrapAdmin => {
+            let conn = db::connect(&args.database_url).await?;
+            let user = bootstrap_admin(&conn).await?;
+            println!("user {} is now an admin", user.id);
         },
     }
 
     Ok(())
 }
 
-async fn hello() -> &'static str {
-    "Hello, world!"
-}
-
-async fn graphql_playgound() -> HttpResponse {
-    HttpResponse::Ok()
-        .content_type("text/html; charset=utf-8")
-        .body(playground_source(GraphQLPlaygroundConfig::new("/graphql")))
-}
-
-async fn handle_graphql(session: Session, schema: web::Data<Schema<QueryRoot, Mutation, EmptySubscription>>, req: GraphQLRequest) -> Result<GraphQLResponse, Error> {
-    let res = handle_graphql_anyhow_result(session, schema, req).await?;
-    Ok(res)
+async fn bootstrap_admin(conn: &DatabaseConnection) -> Result<user::Model> {
+    db::transaction(conn, move |txn| async move {
+        if user::Entity::find().filter(user::Column::Role.eq(Role::Admin)).one(txn).await?.is_some() {
+            bail!("there is an admin already");
+        }
+        let Some(user) = user::Entity::find().order_by_asc(user::Column::RegisteredAt).one(txn).await? else {
+            bail!("no user has registered yet");
+        };
+        let mut user: user::ActiveModel = user.into();
+        user.role = Set(Role::Admin);
+        let user = user.update(txn).await?;
+        Ok(user)
+    }.boxed()).await
 }
 
-async fn handle_graphql_anyhow_result(session: Session, schema: web::Data<Schema<QueryRoot, Mutation, EmptySubscription>>, req: GraphQLRequest) -> Result<GraphQLResponse> {
-    let req = req.into_inner();
-
-    let req = if let Some(user) = session.get::<user::Model>("user")? {
-        req.data(user.clone())
-    } else {
-        req
-    };
-
-    let conn = db::connect().await?;
-
-    let trx = conn.begin().await?;
-    let trx = Arc::new(trx);
-    let res = schema.execute(
-        req.data(Arc::downgrade(&trx)),
-    ).await;
-    let trx = Arc::try_unwrap(trx).expect("only one reference to the transaction should exist");
-    if res.is_err() {
-        let _ = trx.rollback().await;
-        return Ok(res.into());
-    }
-    trx.commit().await?;
-
-    Ok(res.into())
+async fn hello() -> &'static str {
+    "Hello, world!"
 }
-
@@ -0,0 +1,153 @@
+// rendering of post content, written in CommonMark with GitHub extensions, to sanitized HTML and plain text
+
+use std::{num::NonZeroUsize, sync::{Arc, Mutex}};
+use ammonia::Builder;
+use chrono::NaiveDateTime;
+use lru::LruCache;
+use once_cell::sync::Lazy;
+use pulldown_cmark::{html, CodeBlockKind, Event, Options, Parser, Tag, TagEnd};
+use syntect::{
+    html::{ClassedHTMLGenerator, ClassStyle},
+    parsing::SyntaxSet,
+    util::LinesWithEndings,
+};
+use uuid::Uuid;
+
+const WORDS_PER_MINUTE: usize = 200;
+
+static SYNTAX_SET: Lazy<SyntaxSet> = Lazy::new(SyntaxSet::load_defaults_newlines);
+
+/**
+Allows what the renderer emits, minus scripts, event handlers and dangerous URLs, which ammonia always removes.
+*/
+static SANITIZER: Lazy<Builder<'static>> = Lazy::new(|| {
+    let mut builder = Builder::default();
+    builder
+        .add_tags(&["input"])
+        .add_tag_attributes("input", &["type", "checked", "disabled"])
+        .add_tag_attributes("pre", &["class"])
+        .add_tag_attributes("code", &["class"])
+        .add_tag_attributes("span", &["class"]);
+    builder
+});
+
+// rendered HTML by post ID, with the post's `updated_at` it was rendered at
+type HtmlCache = LruCache<Uuid, (NaiveDateTime, Arc<String>)>;
+
+/**
+Rendered HTML by post ID, valid while the post's `updated_at` is unchanged.
+*/
+static HTML_CACHE: Lazy<Mutex<HtmlCache>> =
+    Lazy::new(|| Mutex::new(LruCache::new(NonZeroUsize::new(1024).expect("cache capacity is not zero"))));
+
+fn options() -> Options {
+    Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS | Options::ENABLE_FOOTNOTES
+}
+
+/**
+Renders `markdown` to HTML through [render_html], reusing the result of an earlier call for the same post revision.
+*/
+pub fn cached_html(id: Uuid, updated_at: NaiveDateTime, markdown: &str) -> Arc<String> {
+    let cached = HTML_CACHE.lock().ok().and_then(|mut cache| cache.get(&id).cloned());
+    if let Some((cached_at, html)) = cached {
+        if cached_at == updated_at {
+            return html;
+        }
+    }
+
+    let html = Arc::new(render_html(markdown));
+    if let Ok(mut cache) = HTML_CACHE.lock() {
+        cache.put(id, (updated_at, html.clone()));
+    }
+    html
+}
+
+/**
+Renders `markdown` to sanitized HTML. Fenced code blocks in a known language are highlighted with CSS classes.
+*/
+pub fn render_html(markdown: &str) -> String {
+    let mut events = Vec::new();
+    let mut code_block: Option<(String, String)> = None;
+    for event in Parser::new_ext(markdown, options()) {
+        match event {
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(lang))) if SYNTAX_SET.find_syntax_by_token(&lang).is_some() => {
+                code_block = Some((lang.to_string(), String::new()));
+            },
+            Event::Text(text) if code_block.is_some() => {
+                if let Some((_, code)) = &mut code_block {
+                    code.push_str(&text);
+                }
+            },
+            Event::End(TagEnd::CodeBlock) if code_block.is_some() => {
+                if let Some((lang, code)) = code_block.take() {
+                    events.push(Event::Html(highlight(&lang, &code).into()));
+                }
+            },
+            event => events.push(event),
+        }
+    }
+
+    let mut html = String::new();
+    html::push_html(&mut html, events.into_iter());
+    SANITIZER.clean(&html).to_string()
+}
+
+fn highlight(lang: &str, code: &str) -> String {
+    let class: String = lang.chars().filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_').collect();
+    let highlighted = SYNTAX_SET.find_syntax_by_token(lang).and_then(|syntax| {
+        let mut generator = ClassedHTMLGenerator::new_with_class_style(syntax, &SYNTAX_SET, ClassStyle::Spaced);
+        for line in LinesWithEndings::from(code) {
+            generator.parse_html_for_line_which_includes_newline(line).ok()?;
+        }
+        Some(generator.finalize())
+    });
+    let body = highlighted.unwrap_or_else(|| escape_html(code));
+    format!("<pre><code class=\"language-{}\">{}</code></pre>\n", class, body)
+}
+
+/**
+Escapes `text` for use in HTML text and attribute values.
+*/
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/**
+The text of `markdown` without markup, with whitespace collapsed.
+*/
+pub fn plain_text(markdown: &str) -> String {
+    let mut text = String::new();
+    for event in Parser::new_ext(markdown, options()) {
+        match event {
+            Event::Text(fragment) | Event::Code(fragment) => text.push_str(&fragment),
+            Event::SoftBreak | Event::HardBreak | Event::End(_) => text.push(' '),
+            _ => {},
+        }
+    }
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/**
+At most `length` characters of the plain text, cut at a word boundary when possible and marked with an ellipsis.
+*/
+pub fn excerpt(markdown: &str, length: usize) -> String {
+    let text = plain_text(markdown);
+    if text.chars().count() <= length {
+        return text;
+    }
+
+    let cut: String = text.chars().take(length).collect();
+    let cut = match cut.rfind(' ') {
+        Some(index) if index > 0 => &cut[..index],
+        _ => &cut[..],
+    };
+    format!("{}…", cut.trim_end())
+}
+
+/**
+Estimated minutes to read `markdown`, at least one.
+*/
+pub fn reading_time(markdown: &str) -> usize {
+    let words = plain_text(markdown).split_whitespace().count();
+    words.div_ceil(WORDS_PER_MINUTE).max(1)
+}
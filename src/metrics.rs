@@ -0,0 +1,318 @@
+// Prometheus metrics of the HTTP requests, GraphQL operations, transactions, sessions and passkey ceremonies
+
+use std::{
+    collections::HashSet,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use anyhow::Result;
+use actix_web::{
+    body::MessageBody,
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    http::header,
+    web,
+    HttpRequest,
+    HttpResponse,
+};
+use async_graphql::{
+    async_trait,
+    extensions::{Extension, ExtensionContext, ExtensionFactory, NextExecute, NextParseQuery, NextRequest},
+    parser::types::ExecutableDocument,
+    Response,
+    ServerResult,
+    Value,
+    Variables,
+};
+use futures::future::{ready, LocalBoxFuture, Ready};
+use prometheus::{HistogramOpts, HistogramVec, IntCounterVec, IntGauge, Opts, Registry, TextEncoder};
+
+use super::{constant_time_eq, session, AppState, Error};
+
+/**
+Where `/metrics` is served, configurable from the command line. Without either option it is not served at all.
+*/
+#[derive(Debug, Clone, Default, clap::Args)]
+pub struct MetricsConfig {
+    /// Serve /metrics on the main address to requests bearing this token, unless it is empty
+    #[clap(long, env = "METRICS_TOKEN")]
+    pub metrics_token: Option<String>,
+    /// Serve /metrics without a token on this separate, presumably private, address
+    #[clap(long)]
+    pub metrics_bind: Option<SocketAddr>,
+}
+
+// bounds of the operation name label, whose values the clients choose
+const MAX_OPERATION_NAMES: usize = 100;
+const MAX_OPERATION_NAME_LEN: usize = 64;
+
+/**
+The metrics of an app, in a registry of their own.
+*/
+#[derive(Clone)]
+pub struct Metrics {
+    registry: Registry,
+    http_requests: IntCounterVec,
+    http_request_duration: HistogramVec,
+    graphql_operations: IntCounterVec,
+    graphql_errors: IntCounterVec,
+    transactions: IntCounterVec,
+    transaction_duration: HistogramVec,
+    sessions: IntGauge,
+    webauthn_ceremonies: IntCounterVec,
+    // operation names which have a label value of their own
+    operation_names: Arc<Mutex<HashSet<String>>>,
+}
+
+impl Metrics {
+    pub fn new() -> Result<Self> {
+        let registry = Registry::new();
+        let http_requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP requests by method, route pattern and status"),
+            &["method", "route", "status"],
+        )?;
+        let http_request_duration = HistogramVec::new(
+            HistogramOpts::new("http_request_duration_seconds", "HTTP request latencies by method and route pattern"),
+            &["method", "route"],
+        )?;
+        let graphql_operations = IntCounterVec::new(
+            Opts::new("graphql_operations_total", "Executed GraphQL operations by name and type"),
+            &["operation_name", "operation_type"],
+        )?;
+        let graphql_errors = IntCounterVec::new(
+            Opts::new("graphql_errors_total", "Errors in GraphQL responses by their extension code"),
+            &["code"],
+        )?;
+        let transactions = IntCounterVec::new(
+            Opts::new("db_transactions_total", "Transactions of GraphQL requests by outcome, commit or rollback"),
+            &["outcome"],
+        )?;
+        let transaction_duration = HistogramVec::new(
+            HistogramOpts::new("db_transaction_duration_seconds", "Durations of the transactions of GraphQL requests by outcome"),
+            &["outcome"],
+        )?;
+        let sessions = IntGauge::new("sessions", "Unexpired sessions in the session store")?;
+        let webauthn_ceremonies = IntCounterVec::new(
+            Opts::new("webauthn_ceremonies_total", "Passkey ceremony steps by ceremony, stage and outcome"),
+            &["ceremony", "stage", "outcome"],
+        )?;
+
+        registry.register(Box::new(http_requests.clone()))?;
+        registry.register(Box::new(http_request_duration.clone()))?;
+        registry.register(Box::new(graphql_operations.clone()))?;
+        registry.register(Box::new(graphql_errors.clone()))?;
+        registry.register(Box::new(transactions.clone()))?;
+        registry.register(Box::new(transaction_duration.clone()))?;
+        registry.register(Box::new(sessions.clone()))?;
+        registry.register(Box::new(webauthn_ceremonies.clone()))?;
+
+        Ok(Self {
+            registry,
+            http_requests,
+            http_request_duration,
+            graphql_operations,
+            graphql_errors,
+            transactions,
+            transaction_duration,
+            sessions,
+            webauthn_ceremonies,
+            operation_names: Arc::new(Mutex::new(HashSet::new())),
+        })
+    }
+
+    // the label value of an operation name: the first `MAX_OPERATION_NAMES` names are their own value,
+    // later ones and names longer than `MAX_OPERATION_NAME_LEN` share "other"
+    fn operation_label(&self, operation_name: Option<&str>) -> String {
+        let Some(operation_name) = operation_name else {
+            return "anonymous".to_string();
+        };
+        if operation_name.len() > MAX_OPERATION_NAME_LEN {
+            return "other".to_string();
+        }
+        let Ok(mut operation_names) = self.operation_names.lock() else {
+            return "other".to_string();
+        };
+        if operation_names.contains(operation_name) || operation_names.len() < MAX_OPERATION_NAMES {
+            operation_names.insert(operation_name.to_string());
+            operation_name.to_string()
+        } else {
+            "other".to_string()
+        }
+    }
+
+    fn observe_request(&self, method: &str, route: &str, status: &str, elapsed: Duration) {
+        self.http_requests.with_label_values(&[method, route, status]).inc();
+        self.http_request_duration.with_label_values(&[method, route]).observe(elapsed.as_secs_f64());
+    }
+
+    /// Counts a transaction ending in `outcome`, `commit` or `rollback`, after `elapsed`
+    pub(crate) fn observe_transaction(&self, outcome: &str, elapsed: Duration) {
+        self.transactions.with_label_values(&[outcome]).inc();
+        self.transaction_duration.with_label_values(&[outcome]).observe(elapsed.as_secs_f64());
+    }
+
+    /// Counts a `stage` of a passkey `ceremony`, e.g. the `start` of a `registration`
+    pub(crate) fn count_ceremony(&self, ceremony: &str, stage: &str, succeeded: bool) {
+        let outcome = if succeeded { "success" } else { "failure" };
+        self.webauthn_ceremonies.with_label_values(&[ceremony, stage, outcome]).inc();
+    }
+
+    /// The metrics in the Prometheus text format
+    pub fn render(&self) -> Result<String> {
+        self.sessions.set(session::count() as i64);
+        Ok(TextEncoder::new().encode_to_string(&self.registry.gather())?)
+    }
+}
+
+/**
+Middleware counting the requests and their latencies per route pattern, with the [AppState] of the app.
+*/
+pub struct RequestMetrics;
+
+impl<S, B> Transform<S, ServiceRequest> for RequestMetrics
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Transform = RequestMetricsMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestMetricsMiddleware { service }))
+    }
+}
+
+pub struct RequestMetricsMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestMetricsMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let metrics = req.app_data::<web::Data<AppState>>().map(|state| state.metrics.clone());
+        let method = req.method().to_string();
+        let start = Instant::now();
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            let res = fut.await;
+            if let Some(metrics) = metrics {
+                // route patterns rather than paths, which would make a series per post
+                let (route, status) = match &res {
+                    Ok(res) => (res.request().match_pattern(), res.status()),
+                    Err(err) => (None, err.as_response_error().status_code()),
+                };
+                metrics.observe_request(&method, route.as_deref().unwrap_or("unmatched"), status.as_str(), start.elapsed());
+            }
+            res
+        })
+    }
+}
+
+/**
+Extension factory which counts the executed operations and the errors of the responses.
+*/
+pub struct GraphQLMetrics {
+    metrics: Metrics,
+}
+
+impl GraphQLMetrics {
+    pub fn new(metrics: Metrics) -> Self {
+        Self { metrics }
+    }
+}
+
+impl ExtensionFactory for GraphQLMetrics {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(GraphQLMetricsExtension { metrics: self.metrics.clone(), operations: Mutex::new(Vec::new()) })
+    }
+}
+
+struct GraphQLMetricsExtension {
+    metrics: Metrics,
+    // names and types of the operations in the parsed document
+    operations: Mutex<Vec<(Option<String>, String)>>,
+}
+
+#[async_trait::async_trait]
+impl Extension for GraphQLMetricsExtension {
+    async fn request(&self, ctx: &ExtensionContext<'_>, next: NextRequest<'_>) -> Response {
+        let res = next.run(ctx).await;
+        for err in &res.errors {
+            let code = match err.extensions.as_ref().and_then(|extensions| extensions.get("code")) {
+                Some(Value::String(code)) => code.as_str(),
+                _ => "none",
+            };
+            self.metrics.graphql_errors.with_label_values(&[code]).inc();
+        }
+        res
+    }
+
+    async fn parse_query(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        query: &str,
+        variables: &Variables,
+        next: NextParseQuery<'_>,
+    ) -> ServerResult<ExecutableDocument> {
+        let document = next.run(ctx, query, variables).await?;
+        if let Ok(mut operations) = self.operations.lock() {
+            *operations = document.operations.iter()
+                .map(|(name, operation)| (name.map(|name| name.to_string()), operation.node.ty.to_string()))
+                .collect();
+        }
+        Ok(document)
+    }
+
+    async fn execute(&self, ctx: &ExtensionContext<'_>, operation_name: Option<&str>, next: NextExecute<'_>) -> Response {
+        let operation = self.operations.lock().ok().and_then(|operations| {
+            operations.iter()
+                .find(|(name, _)| operation_name.is_none() || name.as_deref() == operation_name)
+                .cloned()
+        });
+        let res = next.run(ctx, operation_name).await;
+        if let Some((name, operation_type)) = operation {
+            let name = self.metrics.operation_label(name.as_deref());
+            self.metrics.graphql_operations.with_label_values(&[&name, &operation_type]).inc();
+        }
+        res
+    }
+}
+
+/**
+Serves the metrics to requests bearing the configured token, on the main address.
+*/
+pub(crate) async fn scrape_with_token(req: HttpRequest, state: web::Data<AppState>) -> Result<HttpResponse, Error> {
+    let Some(token) = &state.metrics_token else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let bearer = req.headers().get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if !bearer.is_some_and(|bearer| constant_time_eq(bearer.as_bytes(), token.as_bytes())) {
+        return Ok(HttpResponse::Unauthorized().insert_header((header::WWW_AUTHENTICATE, "Bearer")).finish());
+    }
+    scrape(state).await
+}
+
+/**
+Serves the metrics to anyone, on the separate metrics address.
+*/
+pub(crate) async fn scrape(state: web::Data<AppState>) -> Result<HttpResponse, Error> {
+    let body = state.metrics.render()?;
+    Ok(HttpResponse::Ok().content_type(prometheus::TEXT_FORMAT).body(body))
+}
@@ -0,0 +1,55 @@
+// offset based cursor pagination for connection fields taking `first` and `after`
+
+use anyhow::{anyhow, Result};
+use async_graphql::{connection::{Connection, CursorType, Edge}, OutputType};
+
+pub const DEFAULT_PAGE_SIZE: usize = 20;
+pub const MAX_PAGE_SIZE: usize = 100;
+
+/**
+The window of a list requested by `first` and `after`. The cursor of an item is its offset in the whole list.
+*/
+#[derive(Debug, Clone, Copy)]
+pub struct Page {
+    pub offset: u64,
+    pub limit: u64,
+}
+
+impl Page {
+    pub fn new(first: Option<i32>, after: Option<String>) -> Result<Self> {
+        let limit = match first {
+            None => DEFAULT_PAGE_SIZE,
+            Some(first) if first >= 0 && first as usize <= MAX_PAGE_SIZE => first as usize,
+            Some(_) => return Err(anyhow!("first must be between 0 and {}", MAX_PAGE_SIZE)),
+        };
+        let offset = match after {
+            None => 0,
+            Some(after) => usize::decode_cursor(&after).map_err(|_| anyhow!("invalid cursor"))? + 1,
+        };
+        Ok(Self { offset: offset as u64, limit: limit as u64 })
+    }
+
+    /// Number of rows to fetch: one more than the page, to tell whether a next page exists
+    pub fn fetch_limit(&self) -> u64 {
+        self.limit + 1
+    }
+
+    /// Builds the connection from the rows fetched at `offset` with [Page::fetch_limit]
+    pub fn connection<T: OutputType>(&self, mut rows: Vec<T>) -> Connection<usize, T> {
+        let has_next_page = rows.len() as u64 > self.limit;
+        rows.truncate(self.limit as usize);
+
+        let mut connection = Connection::new(self.offset > 0, has_next_page);
+        let offset = self.offset as usize;
+        connection.edges.extend(rows.into_iter().enumerate().map(|(index, row)| Edge::new(offset + index, row)));
+        connection
+    }
+}
+
+/**
+Complexity of a connection field: the child complexity times the requested page size.
+*/
+pub fn complexity(first: Option<i32>, child_complexity: usize) -> usize {
+    let size = first.map(|first| first.clamp(0, MAX_PAGE_SIZE as i32) as usize).unwrap_or(DEFAULT_PAGE_SIZE);
+    size.max(1) * child_complexity
+}
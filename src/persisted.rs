@@ -0,0 +1,192 @@
+// persisted queries: storages for automatic persisted queries (APQ), and the trusted documents allowlist
+
+use std::{collections::HashSet, fs, num::NonZeroUsize, path::{Path, PathBuf}, sync::Arc};
+use anyhow::Result;
+use async_graphql::{
+    async_trait,
+    extensions::{
+        apollo_persisted_queries::{ApolloPersistedQueries, CacheStorage, LruCacheStorage},
+        Extension,
+        ExtensionContext,
+        ExtensionFactory,
+        NextPrepareRequest,
+    },
+    parser::types::ExecutableDocument,
+    Request,
+    SchemaBuilder,
+    ServerError,
+    ServerResult,
+};
+use chrono::Utc;
+use futures::FutureExt;
+use sea_orm::prelude::*;
+use sea_orm::{ActiveValue::Set, sea_query::OnConflict};
+use serde::Deserialize;
+use serde_json::{to_value, from_value};
+use sha2::{Digest, Sha256};
+
+use super::{
+    db,
+    entity::persisted_query,
+};
+
+pub const CODE_NOT_ALLOWED: &str = "PERSISTED_QUERY_NOT_ALLOWED";
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum CacheKind {
+    Disabled,
+    Memory,
+    Sqlite,
+}
+
+/**
+Persisted query settings, configurable from the command line.
+*/
+#[derive(Debug, Clone, clap::Args)]
+pub struct PersistedQueryConfig {
+    /// Storage of automatic persisted queries
+    #[clap(long, value_enum, default_value_t = CacheKind::Memory)]
+    pub apq_cache: CacheKind,
+    /// Capacity of the in-memory APQ cache
+    #[clap(long, default_value = "1024")]
+    pub apq_cache_size: NonZeroUsize,
+    /// File of allowed sha256 query hashes, one per line; any other document is rejected
+    #[clap(long)]
+    pub trusted_documents: Option<PathBuf>,
+}
+
+/**
+Registers the trusted documents check and the APQ extension on a schema, in this order.
+*/
+pub fn register<Query, Mutation, Subscription>(
+    builder: SchemaBuilder<Query, Mutation, Subscription>,
+    config: &PersistedQueryConfig,
+    trusted_documents: Option<&TrustedDocuments>,
+    conn: &DatabaseConnection,
+) -> SchemaBuilder<Query, Mutation, Subscription> {
+    let builder = match trusted_documents {
+        Some(trusted_documents) => builder.extension(trusted_documents.clone()),
+        None => builder,
+    };
+    match config.apq_cache {
+        CacheKind::Disabled => builder,
+        CacheKind::Memory => builder.extension(ApolloPersistedQueries::new(LruCacheStorage::new(config.apq_cache_size.get()))),
+        CacheKind::Sqlite => builder.extension(ApolloPersistedQueries::new(SqliteCacheStorage(conn.clone()))),
+    }
+}
+
+/**
+APQ storage backed by the `persisted_query` table, so that hashes survive restarts and are shared by workers.
+*/
+#[derive(Clone)]
+pub struct SqliteCacheStorage(pub DatabaseConnection);
+
+#[async_trait::async_trait]
+impl CacheStorage for SqliteCacheStorage {
+    async fn get(&self, key: String) -> Option<ExecutableDocument> {
+        match load_document(&self.0, key).await {
+            Ok(document) => document,
+            Err(err) => {
+                tracing::warn!("failed to load persisted query: {:?}", err);
+                None
+            },
+        }
+    }
+
+    async fn set(&self, key: String, query: ExecutableDocument) {
+        if let Err(err) = save_document(&self.0, key, query).await {
+            tracing::warn!("failed to save persisted query: {:?}", err);
+        }
+    }
+}
+
+async fn load_document(conn: &DatabaseConnection, hash: String) -> Result<Option<ExecutableDocument>> {
+    let persisted_query = db::transaction(conn, move |txn| async move {
+        let persisted_query = persisted_query::Entity::find_by_id(hash).one(txn).await?;
+        Ok(persisted_query)
+    }.boxed()).await?;
+
+    let Some(persisted_query) = persisted_query else {
+        return Ok(None);
+    };
+    Ok(Some(from_value(persisted_query.document)?))
+}
+
+async fn save_document(conn: &DatabaseConnection, hash: String, document: ExecutableDocument) -> Result<()> {
+    let document = to_value(document)?;
+    db::transaction(conn, move |txn| async move {
+        let persisted_query = persisted_query::ActiveModel {
+            hash: Set(hash),
+            document: Set(document),
+            created_at: Set(Utc::now().naive_utc()),
+        };
+        persisted_query::Entity::insert(persisted_query)
+            .on_conflict(OnConflict::column(persisted_query::Column::Hash).do_nothing().to_owned())
+            .exec_without_returning(txn)
+            .await?;
+        Ok(())
+    }.boxed()).await
+}
+
+/**
+Allowlist of query hashes loaded at startup. Registered as an extension, it rejects every document not in the list.
+*/
+#[derive(Clone)]
+pub struct TrustedDocuments(Arc<HashSet<String>>);
+
+impl TrustedDocuments {
+    /// Reads one hex sha256 hash per line; blank lines and lines starting with `#` are ignored
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)?;
+        let hashes = content.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(str::to_lowercase)
+            .collect();
+        Ok(Self(Arc::new(hashes)))
+    }
+}
+
+impl ExtensionFactory for TrustedDocuments {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(TrustedDocumentsExtension { hashes: self.0.clone() })
+    }
+}
+
+struct TrustedDocumentsExtension {
+    hashes: Arc<HashSet<String>>,
+}
+
+#[derive(Deserialize)]
+struct PersistedQueryHash {
+    #[serde(rename = "sha256Hash")]
+    sha256_hash: String,
+}
+
+#[async_trait::async_trait]
+impl Extension for TrustedDocumentsExtension {
+    async fn prepare_request(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        request: Request,
+        next: NextPrepareRequest<'_>,
+    ) -> ServerResult<Request> {
+        let hash = if request.query.is_empty() {
+            // hash-only APQ request, checked before the APQ storage is consulted
+            request.extensions.get("persistedQuery")
+                .and_then(|value| async_graphql::from_value::<PersistedQueryHash>(value.clone()).ok())
+                .map(|persisted_query| persisted_query.sha256_hash.to_lowercase())
+        } else {
+            Some(format!("{:x}", Sha256::digest(request.query.as_bytes())))
+        };
+
+        match hash {
+            Some(hash) if self.hashes.contains(&hash) => next.run(ctx, request).await,
+            _ => {
+                let mut err = ServerError::new("Only trusted documents are allowed.", None);
+                err.extensions.get_or_insert_with(Default::default).set("code", CODE_NOT_ALLOWED);
+                Err(err)
+            },
+        }
+    }
+}
@@ -0,0 +1,121 @@
+// filtering and ordering arguments of post lists
+
+use anyhow::Result;
+use async_graphql::{Enum, InputObject};
+use chrono::NaiveDateTime;
+use sea_orm::prelude::*;
+use sea_orm::{
+    sea_query::{Expr, LikeExpr},
+    Condition,
+    Order,
+    QueryOrder,
+    QuerySelect,
+    QueryTrait,
+    Select,
+};
+
+use super::entity::{post, user};
+
+/**
+Conditions a post must all meet. Date ranges include their start and exclude their end.
+*/
+#[derive(Debug, Default, InputObject)]
+pub struct PostFilter {
+    pub author_id: Option<String>,
+    pub author_slug: Option<String>,
+    pub created_after: Option<NaiveDateTime>,
+    pub created_before: Option<NaiveDateTime>,
+    pub updated_after: Option<NaiveDateTime>,
+    pub updated_before: Option<NaiveDateTime>,
+    /// Case-insensitive for ASCII letters
+    pub title_contains: Option<String>,
+    pub has_slug: Option<bool>,
+}
+
+impl PostFilter {
+    pub fn condition(&self) -> Result<Condition> {
+        let mut condition = Condition::all();
+        if let Some(author_id) = &self.author_id {
+            condition = condition.add(post::Column::UserId.eq(Uuid::parse_str(author_id)?));
+        }
+        if let Some(author_slug) = &self.author_slug {
+            let author_ids = user::Entity::find()
+                .select_only()
+                .column(user::Column::Id)
+                .filter(user::Column::Slug.eq(author_slug.as_str()))
+                .into_query();
+            condition = condition.add(post::Column::UserId.in_subquery(author_ids));
+        }
+        if let Some(created_after) = self.created_after {
+            condition = condition.add(post::Column::CreatedAt.gte(created_after));
+        }
+        if let Some(created_before) = self.created_before {
+            condition = condition.add(post::Column::CreatedAt.lt(created_before));
+        }
+        if let Some(updated_after) = self.updated_after {
+            condition = condition.add(post::Column::UpdatedAt.gte(updated_after));
+        }
+        if let Some(updated_before) = self.updated_before {
+            condition = condition.add(post::Column::UpdatedAt.lt(updated_before));
+        }
+        if let Some(title_contains) = &self.title_contains {
+            condition = condition.add(Expr::col((post::Entity, post::Column::Title)).like(contains(title_contains)));
+        }
+        match self.has_slug {
+            Some(true) => condition = condition.add(post::Column::Slug.is_not_null()),
+            Some(false) => condition = condition.add(post::Column::Slug.is_null()),
+            None => {},
+        }
+        Ok(condition)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Enum)]
+pub enum PostOrderField {
+    #[default]
+    CreatedAt,
+    UpdatedAt,
+    Title,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Enum)]
+pub enum OrderDirection {
+    Asc,
+    #[default]
+    Desc,
+}
+
+/**
+Order of a post list, newest first by default. Posts which are equal in the field are ordered by ID.
+*/
+#[derive(Debug, Default, InputObject)]
+pub struct PostOrder {
+    #[graphql(default)]
+    pub field: PostOrderField,
+    #[graphql(default)]
+    pub direction: OrderDirection,
+}
+
+impl PostOrder {
+    pub fn apply(&self, select: Select<post::Entity>) -> Select<post::Entity> {
+        // the default order, with its ID tiebreak, is served by idx_post_created_at on (created_at, id)
+        let column = match self.field {
+            PostOrderField::CreatedAt => post::Column::CreatedAt,
+            PostOrderField::UpdatedAt => post::Column::UpdatedAt,
+            PostOrderField::Title => post::Column::Title,
+        };
+        let order = match self.direction {
+            OrderDirection::Asc => Order::Asc,
+            OrderDirection::Desc => Order::Desc,
+        };
+        select.order_by(column, order.clone()).order_by(post::Column::Id, order)
+    }
+}
+
+/**
+`LIKE` pattern matching any text which contains `text`.
+*/
+pub fn contains(text: &str) -> LikeExpr {
+    let escaped = text.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+    LikeExpr::new(format!("%{}%", escaped)).escape('\\')
+}
@@ -0,0 +1,195 @@
+// rate limits of the passkey ceremonies, by client address and by target user, and lockouts after failed logins
+
+use std::{
+    hash::Hash,
+    num::NonZeroUsize,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use lru::LruCache;
+use uuid::Uuid;
+
+use super::Error;
+
+// most buckets or lockouts kept per limit, past which the least recently used one is dropped
+const MAX_ENTRIES: usize = 10_000;
+
+/**
+Limits of the passkey ceremonies, configurable from the command line.
+*/
+#[derive(Debug, Clone, clap::Args)]
+pub struct RateLimitConfig {
+    /// Ceremony starts a client address may make per minute, on average
+    #[clap(long, default_value_t = 20.0)]
+    pub auth_ip_rate_per_minute: f64,
+    /// Ceremony starts a client address may make at once, more than a user as addresses may be shared
+    #[clap(long, default_value_t = 10)]
+    pub auth_ip_burst: u32,
+    /// Logins a target user may be asked for per minute, on average
+    #[clap(long, default_value_t = 10.0)]
+    pub auth_user_rate_per_minute: f64,
+    /// Logins a target user may be asked for at once
+    #[clap(long, default_value_t = 5)]
+    pub auth_user_burst: u32,
+    /// Failed logins of a user after which further logins are locked out
+    #[clap(long, default_value_t = 5)]
+    pub lockout_after: u32,
+    /// Length of the first lockout, in seconds, which doubles with every further failure
+    #[clap(long, default_value_t = 30)]
+    pub lockout_secs: u64,
+    /// Longest lockout, in seconds, after which the failures are forgotten too
+    #[clap(long, default_value_t = 3600)]
+    pub lockout_max_secs: u64,
+}
+
+/**
+The limits of the auth endpoints, shared by the server workers.
+*/
+pub struct AuthLimiter {
+    by_ip: TokenBuckets<String>,
+    by_user: TokenBuckets<Uuid>,
+    lockouts: Lockouts,
+}
+
+impl AuthLimiter {
+    pub fn new(config: &RateLimitConfig) -> Self {
+        Self {
+            by_ip: TokenBuckets::new(config.auth_ip_rate_per_minute / 60.0, config.auth_ip_burst),
+            by_user: TokenBuckets::new(config.auth_user_rate_per_minute / 60.0, config.auth_user_burst),
+            lockouts: Lockouts {
+                after: config.lockout_after,
+                base: Duration::from_secs(config.lockout_secs),
+                max: Duration::from_secs(config.lockout_max_secs),
+                failures: Mutex::new(LruCache::new(max_entries())),
+            },
+        }
+    }
+
+    /// Takes a token of the client address `ip`, if any
+    pub(crate) fn check_ip(&self, ip: Option<&str>) -> Result<(), Error> {
+        self.by_ip.take(ip.unwrap_or("unknown").to_string()).map_err(too_many_requests)
+    }
+
+    /// Takes a token of the user `user_id`, who must not be locked out
+    pub(crate) fn check_user(&self, user_id: Uuid) -> Result<(), Error> {
+        self.check_lockout(user_id)?;
+        self.by_user.take(user_id).map_err(too_many_requests)
+    }
+
+    pub(crate) fn check_lockout(&self, user_id: Uuid) -> Result<(), Error> {
+        self.lockouts.check(user_id).map_err(too_many_requests)
+    }
+
+    pub(crate) fn login_failed(&self, user_id: Uuid) {
+        self.lockouts.failed(user_id);
+    }
+
+    pub(crate) fn login_succeeded(&self, user_id: Uuid) {
+        self.lockouts.succeeded(user_id);
+    }
+}
+
+fn too_many_requests(retry_after: Duration) -> Error {
+    Error::TooManyRequests { retry_after }
+}
+
+fn max_entries() -> NonZeroUsize {
+    NonZeroUsize::new(MAX_ENTRIES).expect("capacity is not zero")
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+// token buckets refilled at `rate` tokens per second up to `burst` tokens, at most [MAX_ENTRIES] of them
+struct TokenBuckets<K> {
+    rate: f64,
+    burst: f64,
+    buckets: Mutex<LruCache<K, Bucket>>,
+}
+
+impl<K: Eq + Hash> TokenBuckets<K> {
+    fn new(rate: f64, burst: u32) -> Self {
+        Self { rate, burst: f64::from(burst), buckets: Mutex::new(LruCache::new(max_entries())) }
+    }
+
+    // takes a token of `key`, or tells how long until there is one
+    fn take(&self, key: K) -> Result<(), Duration> {
+        let Ok(mut buckets) = self.buckets.lock() else {
+            return Ok(());
+        };
+        let now = Instant::now();
+        // a new key in a full cache evicts the least recently used bucket, which is full again by then unless
+        // more than MAX_ENTRIES keys were used within a refill
+        let bucket = buckets.get_or_insert_mut(key, || Bucket { tokens: self.burst, updated: now });
+        bucket.tokens = (bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * self.rate).min(self.burst);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::try_from_secs_f64((1.0 - bucket.tokens) / self.rate).unwrap_or(Duration::MAX))
+        }
+    }
+}
+
+struct Failures {
+    count: u32,
+    last: Instant,
+}
+
+// consecutive failed logins per user, locking the user out for a time doubling with every failure past `after`,
+// for at most [MAX_ENTRIES] users
+struct Lockouts {
+    after: u32,
+    base: Duration,
+    max: Duration,
+    failures: Mutex<LruCache<Uuid, Failures>>,
+}
+
+impl Lockouts {
+    fn check(&self, user_id: Uuid) -> Result<(), Duration> {
+        let Ok(failures) = self.failures.lock() else {
+            return Ok(());
+        };
+        let Some(failures) = failures.peek(&user_id) else {
+            return Ok(());
+        };
+        let locked_for = self.lockout(failures.count);
+        match locked_for.checked_sub(failures.last.elapsed()) {
+            Some(remaining) if !remaining.is_zero() => Err(remaining),
+            _ => Ok(()),
+        }
+    }
+
+    fn failed(&self, user_id: Uuid) {
+        let Ok(mut failures) = self.failures.lock() else {
+            return;
+        };
+        let now = Instant::now();
+        // the least recently failed user is dropped, whose lockout is over by then unless more than MAX_ENTRIES
+        // users failed within the longest lockout
+        let failures = failures.get_or_insert_mut(user_id, || Failures { count: 0, last: now });
+        if now.duration_since(failures.last) >= self.max {
+            failures.count = 0;
+        }
+        failures.count += 1;
+        failures.last = now;
+    }
+
+    fn succeeded(&self, user_id: Uuid) {
+        if let Ok(mut failures) = self.failures.lock() {
+            failures.pop(&user_id);
+        }
+    }
+
+    // how long `count` consecutive failures lock a user out
+    fn lockout(&self, count: u32) -> Duration {
+        if count < self.after {
+            return Duration::ZERO;
+        }
+        let doublings = (count - self.after).min(31);
+        self.base.saturating_mul(1 << doublings).min(self.max)
+    }
+}
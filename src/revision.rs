@@ -0,0 +1,80 @@
+// post revision history: snapshots taken on every change to a post, and diffs between them
+
+use anyhow::{anyhow, Result};
+use chrono::{Utc, NaiveDateTime};
+use sea_orm::prelude::*;
+use sea_orm::{ActiveValue::Set, DatabaseTransaction, QueryOrder};
+use similar::TextDiff;
+
+use super::entity::{post, post_revision};
+
+/**
+Snapshots the current state of `post` as a revision made by `user_id`.
+*/
+pub async fn record(trx: &DatabaseTransaction, post: &post::Model, user_id: Uuid) -> Result<post_revision::Model> {
+    let revision = snapshot(post, user_id, Utc::now().naive_utc()).insert(trx).await?;
+    Ok(revision)
+}
+
+/**
+Posts written before revisions existed have none; this records their current state, attributed to the post author.
+*/
+pub async fn ensure_baseline(trx: &DatabaseTransaction, post: &post::Model) -> Result<()> {
+    let revisions = post_revision::Entity::find()
+        .filter(post_revision::Column::PostId.eq(post.id))
+        .count(trx)
+        .await?;
+    if revisions == 0 {
+        snapshot(post, post.user_id, post.updated_at).insert(trx).await?;
+    }
+    Ok(())
+}
+
+fn snapshot(post: &post::Model, user_id: Uuid, created_at: NaiveDateTime) -> post_revision::ActiveModel {
+    post_revision::ActiveModel {
+        id: Set(Uuid::new_v4()),
+        post_id: Set(post.id),
+        user_id: Set(user_id),
+        slug: Set(post.slug.clone()),
+        title: Set(post.title.clone()),
+        content: Set(post.content.clone()),
+        created_at: Set(created_at),
+    }
+}
+
+pub async fn list(trx: &DatabaseTransaction, post_id: Uuid) -> Result<Vec<post_revision::Model>> {
+    let revisions = post_revision::Entity::find()
+        .filter(post_revision::Column::PostId.eq(post_id))
+        .order_by_asc(post_revision::Column::CreatedAt)
+        .all(trx)
+        .await?;
+    Ok(revisions)
+}
+
+pub async fn find(trx: &DatabaseTransaction, post_id: Uuid, id: &str) -> Result<post_revision::Model> {
+    let id = Uuid::parse_str(id)?;
+    let revision = post_revision::Entity::find_by_id(id)
+        .filter(post_revision::Column::PostId.eq(post_id))
+        .one(trx)
+        .await?;
+    revision.ok_or_else(|| anyhow!("revision not found"))
+}
+
+/**
+Unified diff between two revisions, each rendered as its title and slug followed by the content.
+*/
+pub fn unified_diff(from: &post_revision::Model, to: &post_revision::Model) -> String {
+    let (from_text, to_text) = (render(from), render(to));
+    TextDiff::from_lines(&from_text, &to_text)
+        .unified_diff()
+        .header(&from.id.to_string(), &to.id.to_string())
+        .to_string()
+}
+
+fn render(revision: &post_revision::Model) -> String {
+    let mut text = format!("title: {}\nslug: {}\n\n{}", revision.title, revision.slug.as_deref().unwrap_or(""), revision.content);
+    if !text.ends_with('\n') {
+        text.push('\n');
+    }
+    text
+}
@@ -0,0 +1,52 @@
+// field guards for role based and per-user authorization
+
+use async_graphql::{Context, Guard, Result};
+use uuid::Uuid;
+
+use super::entity::{sea_orm_active_enums::Role, user};
+
+/**
+Allows a field to authenticated users whose role includes `role`.
+*/
+pub struct RoleGuard {
+    role: Role,
+}
+
+impl RoleGuard {
+    pub fn new(role: Role) -> Self {
+        Self { role }
+    }
+}
+
+impl Guard for RoleGuard {
+    async fn check(&self, ctx: &Context<'_>) -> Result<()> {
+        match ctx.data_opt::<user::Model>() {
+            Some(user) if user.role.includes(self.role) => Ok(()),
+            Some(_) => Err("forbidden".into()),
+            None => Err("unauthenticated".into()),
+        }
+    }
+}
+
+/**
+Allows a field only to the user with ID `user_id`, for data about the user themselves.
+*/
+pub struct SelfGuard {
+    user_id: Uuid,
+}
+
+impl SelfGuard {
+    pub fn new(user_id: Uuid) -> Self {
+        Self { user_id }
+    }
+}
+
+impl Guard for SelfGuard {
+    async fn check(&self, ctx: &Context<'_>) -> Result<()> {
+        match ctx.data_opt::<user::Model>() {
+            Some(user) if user.id == self.user_id => Ok(()),
+            Some(_) => Err("forbidden".into()),
+            None => Err("unauthenticated".into()),
+        }
+    }
+}
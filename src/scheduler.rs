@@ -0,0 +1,43 @@
+// background task which publishes scheduled posts once their time arrives
+
+use std::time::Duration;
+use anyhow::Result;
+use chrono::Utc;
+use futures::FutureExt;
+use sea_orm::prelude::*;
+use sea_orm::ActiveValue::Set;
+
+use super::{
+    db,
+    entity::{post, sea_orm_active_enums::PostStatus},
+};
+
+pub fn spawn(conn: DatabaseConnection, interval: Duration) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(interval);
+        loop {
+            interval.tick().await;
+            match publish_scheduled_posts(&conn).await {
+                Ok(0) => {},
+                Ok(count) => tracing::info!("published {} scheduled posts", count),
+                Err(err) => tracing::warn!("failed to publish scheduled posts: {:?}", err),
+            }
+        }
+    });
+}
+
+pub async fn publish_scheduled_posts(conn: &DatabaseConnection) -> Result<u64> {
+    db::transaction(conn, move |txn| async move {
+        let now = Utc::now().naive_utc();
+        let res = post::Entity::update_many()
+            .set(post::ActiveModel {
+                status: Set(PostStatus::Published),
+                ..Default::default()
+            })
+            .filter(post::Column::Status.eq(PostStatus::Scheduled))
+            .filter(post::Column::PublishedAt.lte(now))
+            .exec(txn)
+            .await?;
+        Ok(res.rows_affected)
+    }.boxed()).await
+}
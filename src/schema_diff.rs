@@ -0,0 +1,159 @@
+// classification of changes between two SDL documents, from the point of view of existing clients
+
+use std::{collections::BTreeMap, fmt};
+use anyhow::Result;
+use async_graphql::parser::{
+    parse_schema,
+    types::{BaseType, FieldDefinition, InputValueDefinition, Type, TypeDefinition, TypeKind, TypeSystemDefinition},
+    Positioned,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Breaking,
+    NonBreaking,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Breaking => "BREAKING",
+            Severity::NonBreaking => "NON-BREAKING",
+        };
+        write!(f, "{}: {}", severity, self.message)
+    }
+}
+
+/**
+Lists the changes from `old` to `new`, breaking changes first.
+*/
+pub fn diff(old: &str, new: &str) -> Result<Vec<Change>> {
+    let old = type_definitions(old)?;
+    let new = type_definitions(new)?;
+
+    let mut changes = Vec::new();
+    for (name, old_type) in &old {
+        match new.get(name) {
+            None => changes.push(breaking(format!("type `{}` was removed", name))),
+            Some(new_type) => diff_type(name, old_type, new_type, &mut changes),
+        }
+    }
+    for name in new.keys().filter(|name| !old.contains_key(*name)) {
+        changes.push(non_breaking(format!("type `{}` was added", name)));
+    }
+
+    changes.sort_by_key(|change| change.severity);
+    Ok(changes)
+}
+
+fn type_definitions(sdl: &str) -> Result<BTreeMap<String, TypeDefinition>> {
+    let document = parse_schema(sdl)?;
+    Ok(document.definitions.into_iter().filter_map(|definition| match definition {
+        TypeSystemDefinition::Type(definition) => Some((definition.node.name.node.to_string(), definition.node)),
+        _ => None,
+    }).collect())
+}
+
+fn diff_type(name: &str, old: &TypeDefinition, new: &TypeDefinition, changes: &mut Vec<Change>) {
+    match (&old.kind, &new.kind) {
+        (TypeKind::Scalar, TypeKind::Scalar) => {},
+        (TypeKind::Object(old), TypeKind::Object(new)) => {
+            diff_names(name, "interface", &old.implements, &new.implements, changes);
+            diff_fields(name, &old.fields, &new.fields, changes);
+        },
+        (TypeKind::Interface(old), TypeKind::Interface(new)) => {
+            diff_names(name, "interface", &old.implements, &new.implements, changes);
+            diff_fields(name, &old.fields, &new.fields, changes);
+        },
+        (TypeKind::Union(old), TypeKind::Union(new)) => {
+            diff_names(name, "member", &old.members, &new.members, changes);
+        },
+        (TypeKind::Enum(old), TypeKind::Enum(new)) => {
+            let old_values: Vec<_> = old.values.iter().map(|value| value.node.value.clone()).collect();
+            let new_values: Vec<_> = new.values.iter().map(|value| value.node.value.clone()).collect();
+            diff_names(name, "value", &old_values, &new_values, changes);
+        },
+        (TypeKind::InputObject(old), TypeKind::InputObject(new)) => {
+            diff_input_values(&format!("input field of `{}`", name), &old.fields, &new.fields, changes);
+        },
+        _ => changes.push(breaking(format!("type `{}` changed its kind", name))),
+    }
+}
+
+fn diff_names(name: &str, what: &str, old: &[Positioned<async_graphql::Name>], new: &[Positioned<async_graphql::Name>], changes: &mut Vec<Change>) {
+    for old_name in old.iter().filter(|old_name| !new.iter().any(|new_name| new_name.node == old_name.node)) {
+        changes.push(breaking(format!("{} `{}` was removed from `{}`", what, old_name.node, name)));
+    }
+    for new_name in new.iter().filter(|new_name| !old.iter().any(|old_name| old_name.node == new_name.node)) {
+        changes.push(non_breaking(format!("{} `{}` was added to `{}`", what, new_name.node, name)));
+    }
+}
+
+fn diff_fields(name: &str, old: &[Positioned<FieldDefinition>], new: &[Positioned<FieldDefinition>], changes: &mut Vec<Change>) {
+    for old_field in old {
+        let field_name = &old_field.node.name.node;
+        let Some(new_field) = new.iter().find(|new_field| &new_field.node.name.node == field_name) else {
+            changes.push(breaking(format!("field `{}.{}` was removed", name, field_name)));
+            continue;
+        };
+
+        let (old_ty, new_ty) = (&old_field.node.ty.node, &new_field.node.ty.node);
+        if old_ty != new_ty {
+            let message = format!("field `{}.{}` changed type from `{}` to `{}`", name, field_name, old_ty, new_ty);
+            // clients keep working when a field gets stricter
+            changes.push(if is_narrowed(old_ty, new_ty) { non_breaking(message) } else { breaking(message) });
+        }
+        diff_input_values(&format!("argument of `{}.{}`", name, field_name), &old_field.node.arguments, &new_field.node.arguments, changes);
+    }
+    for new_field in new.iter().filter(|new_field| !old.iter().any(|old_field| old_field.node.name.node == new_field.node.name.node)) {
+        changes.push(non_breaking(format!("field `{}.{}` was added", name, new_field.node.name.node)));
+    }
+}
+
+fn diff_input_values(what: &str, old: &[Positioned<InputValueDefinition>], new: &[Positioned<InputValueDefinition>], changes: &mut Vec<Change>) {
+    for old_value in old {
+        let value_name = &old_value.node.name.node;
+        let Some(new_value) = new.iter().find(|new_value| &new_value.node.name.node == value_name) else {
+            changes.push(breaking(format!("{} `{}` was removed", what, value_name)));
+            continue;
+        };
+
+        let (old_ty, new_ty) = (&old_value.node.ty.node, &new_value.node.ty.node);
+        if old_ty != new_ty {
+            let message = format!("{} `{}` changed type from `{}` to `{}`", what, value_name, old_ty, new_ty);
+            // clients keep working when an input gets looser
+            changes.push(if is_narrowed(new_ty, old_ty) { non_breaking(message) } else { breaking(message) });
+        }
+    }
+    for new_value in new.iter().filter(|new_value| !old.iter().any(|old_value| old_value.node.name.node == new_value.node.name.node)) {
+        let required = !new_value.node.ty.node.nullable && new_value.node.default_value.is_none();
+        let message = format!("{} `{}` was added", what, new_value.node.name.node);
+        changes.push(if required { breaking(message) } else { non_breaking(message) });
+    }
+}
+
+// whether `narrow` is `wide` with some nullable positions made non-null
+fn is_narrowed(wide: &Type, narrow: &Type) -> bool {
+    if !wide.nullable && narrow.nullable {
+        return false;
+    }
+    match (&wide.base, &narrow.base) {
+        (BaseType::Named(wide), BaseType::Named(narrow)) => wide == narrow,
+        (BaseType::List(wide), BaseType::List(narrow)) => is_narrowed(wide, narrow),
+        _ => false,
+    }
+}
+
+fn breaking(message: String) -> Change {
+    Change { severity: Severity::Breaking, message }
+}
+
+fn non_breaking(message: String) -> Change {
+    Change { severity: Severity::NonBreaking, message }
+}
@@ -0,0 +1,205 @@
+// full-text search over posts, ranked by the FTS5 index where it exists and by substring matching otherwise
+
+use std::collections::HashMap;
+use anyhow::{anyhow, Result};
+use async_graphql::{connection::Connection, SimpleObject};
+use sea_orm::prelude::*;
+use sea_orm::{
+    sea_query::{Alias, Expr, Order},
+    Condition,
+    DatabaseTransaction,
+    DbBackend,
+    QueryOrder,
+    QuerySelect,
+    QueryTrait,
+    Statement,
+};
+
+use super::{
+    entity::{post, user},
+    markdown,
+    pagination::Page,
+    post_filter::contains,
+};
+
+// around the matches in FTS5 snippets
+const SNIPPET_TOKENS: usize = 16;
+// of the excerpt used as snippet without FTS5
+const FALLBACK_SNIPPET_LENGTH: usize = 200;
+
+// highlight delimiters, which can't occur in the text, replaced with `<mark>` tags once the snippet is escaped
+const MARK_START: char = '\u{2}';
+const MARK_END: char = '\u{3}';
+
+/**
+A post matching a search, with the part of its content which matched.
+*/
+#[derive(SimpleObject)]
+#[graphql(name = "PostSearchResult")]
+pub struct SearchResult {
+    pub post: post::Model,
+    /// HTML excerpt of the content, with the matching terms in `<mark>` elements
+    pub snippet: String,
+}
+
+enum Term {
+    Word(String),
+    Prefix(String),
+    Phrase(String),
+}
+
+impl Term {
+    fn text(&self) -> &str {
+        match self {
+            Self::Word(text) | Self::Prefix(text) | Self::Phrase(text) => text,
+        }
+    }
+}
+
+/**
+Searches the posts `user` may read. Words in `query` must all match, `"quoted words"` match as a phrase and
+`word*` matches as a prefix.
+*/
+pub async fn search_posts(trx: &DatabaseTransaction, user: Option<&user::Model>, query: &str, page: Page) -> Result<Connection<usize, SearchResult>> {
+    let terms = parse_query(query);
+    if terms.is_empty() {
+        return Err(anyhow!("search query has no words"));
+    }
+
+    let results = if fts_available(trx).await? {
+        search_fts(trx, user, &terms, page).await?
+    } else {
+        search_like(trx, user, &terms, page).await?
+    };
+    Ok(page.connection(results))
+}
+
+fn parse_query(query: &str) -> Vec<Term> {
+    let mut terms = Vec::new();
+    let mut rest = query;
+    while let Some(start) = rest.find(|c: char| !c.is_whitespace()) {
+        rest = &rest[start..];
+        let term = if let Some(quoted) = rest.strip_prefix('"') {
+            let end = quoted.find('"').unwrap_or(quoted.len());
+            let phrase = quoted[..end].split_whitespace().collect::<Vec<_>>().join(" ");
+            rest = quoted.get(end + 1..).unwrap_or("");
+            Term::Phrase(phrase)
+        } else {
+            let end = rest.find(|c: char| c.is_whitespace() || c == '"').unwrap_or(rest.len());
+            let word = &rest[..end];
+            rest = &rest[end..];
+            match word.trim_end_matches('*') {
+                prefix if prefix.len() < word.len() => Term::Prefix(prefix.to_string()),
+                _ => Term::Word(word.to_string()),
+            }
+        };
+        // punctuation alone is not indexed
+        if term.text().chars().any(char::is_alphanumeric) {
+            terms.push(term);
+        }
+    }
+    terms
+}
+
+async fn fts_available(trx: &DatabaseTransaction) -> Result<bool> {
+    if trx.get_database_backend() != DbBackend::Sqlite {
+        return Ok(false);
+    }
+    let table = trx.query_one(Statement::from_string(
+        DbBackend::Sqlite,
+        "SELECT name FROM sqlite_master WHERE type = 'table' AND name = 'post_fts'",
+    )).await?;
+    Ok(table.is_some())
+}
+
+async fn search_fts(trx: &DatabaseTransaction, user: Option<&user::Model>, terms: &[Term], page: Page) -> Result<Vec<SearchResult>> {
+    // every term is quoted, so that user input can't use the rest of the FTS5 query syntax
+    let fts_query = terms.iter().map(|term| match term {
+        Term::Word(text) | Term::Phrase(text) => format!("\"{}\"", text.replace('"', "\"\"")),
+        Term::Prefix(text) => format!("\"{}\"*", text.replace('"', "\"\"")),
+    }).collect::<Vec<_>>().join(" ");
+    let snippet = format!("snippet(post_fts, 2, '{}', '{}', '…', {})", MARK_START, MARK_END, SNIPPET_TOKENS);
+
+    let mut select = post::Entity::find()
+        .select_only()
+        .column(post::Column::Id)
+        .expr(Expr::cust(snippet))
+        .filter(post::visible_to(user))
+        .filter(Expr::cust_with_values("post_fts MATCH ?", [fts_query]))
+        .offset(page.offset)
+        .limit(page.fetch_limit());
+    QueryTrait::query(&mut select)
+        .inner_join(
+            Alias::new("post_fts"),
+            Expr::col((Alias::new("post_fts"), Alias::new("post_id"))).equals((post::Entity, post::Column::Id)),
+        )
+        .order_by_expr(Expr::cust("bm25(post_fts)"), Order::Asc);
+    let rows: Vec<(Uuid, String)> = select.into_tuple().all(trx).await?;
+
+    let ids: Vec<Uuid> = rows.iter().map(|(id, _)| *id).collect();
+    let mut posts: HashMap<Uuid, post::Model> = post::Entity::find()
+        .filter(post::Column::Id.is_in(ids))
+        .all(trx).await?
+        .into_iter()
+        .map(|post| (post.id, post))
+        .collect();
+    Ok(rows.into_iter().filter_map(|(id, snippet)| {
+        Some(SearchResult { post: posts.remove(&id)?, snippet: snippet_html(&snippet) })
+    }).collect())
+}
+
+async fn search_like(trx: &DatabaseTransaction, user: Option<&user::Model>, terms: &[Term], page: Page) -> Result<Vec<SearchResult>> {
+    let mut condition = Condition::all().add(post::visible_to(user));
+    for term in terms {
+        // a substring match covers prefixes as well
+        condition = condition.add(Condition::any()
+            .add(Expr::col((post::Entity, post::Column::Title)).like(contains(term.text())))
+            .add(Expr::col((post::Entity, post::Column::Content)).like(contains(term.text()))));
+    }
+
+    let posts = post::Entity::find()
+        .filter(condition)
+        .order_by_desc(post::Column::UpdatedAt)
+        .offset(page.offset)
+        .limit(page.fetch_limit())
+        .all(trx).await?;
+    Ok(posts.into_iter().map(|post| {
+        let snippet = mark_terms(&markdown::excerpt(&post.content, FALLBACK_SNIPPET_LENGTH), terms);
+        SearchResult { snippet: snippet_html(&snippet), post }
+    }).collect())
+}
+
+// delimits the ASCII case-insensitive occurrences of `terms` in `text` with the highlight delimiters
+fn mark_terms(text: &str, terms: &[Term]) -> String {
+    // ASCII lowercasing keeps byte offsets, so matches in `lower` are at char boundaries of `text`
+    let lower = text.to_ascii_lowercase();
+    let mut ranges: Vec<(usize, usize)> = terms.iter()
+        .flat_map(|term| {
+            let needle = term.text().to_ascii_lowercase();
+            lower.match_indices(&needle).map(|(start, found)| (start, start + found.len())).collect::<Vec<_>>()
+        })
+        .collect();
+    ranges.sort_unstable();
+
+    let mut marked = String::with_capacity(text.len());
+    let mut position = 0;
+    for (start, end) in ranges {
+        if end <= position {
+            continue;
+        }
+        let start = start.max(position);
+        marked.push_str(&text[position..start]);
+        marked.push(MARK_START);
+        marked.push_str(&text[start..end]);
+        marked.push(MARK_END);
+        position = end;
+    }
+    marked.push_str(&text[position..]);
+    marked
+}
+
+fn snippet_html(snippet: &str) -> String {
+    markdown::escape_html(snippet)
+        .replace(MARK_START, "<mark>")
+        .replace(MARK_END, "</mark>")
+}
@@ -11,6 +11,7 @@ use anyhow::anyhow;
 use chrono::Utc;
 use once_cell::sync::Lazy;
 use rand::distributions::{Alphanumeric, DistString};
+use uuid::Uuid;
 
 /**
 Static map where session states are stored
@@ -18,16 +19,59 @@ Static map where session states are stored
 static SESSION_STATES: Lazy<Mutex<HashMap<String, State>>> =
     Lazy::new(|| Mutex::new(HashMap::new()));
 
+/**
+Expiry times of the unexpired sessions in which the user with ID `user_id` is logged in.
+*/
+pub(crate) fn valid_until_of_user(user_id: Uuid) -> Vec<chrono::DateTime<Utc>> {
+    let Ok(states) = SESSION_STATES.lock() else {
+        return Vec::new();
+    };
+    let now = Utc::now();
+    states.values()
+        .filter(|state| state.valid_until >= now && state.is_of_user(user_id))
+        .map(|state| state.valid_until)
+        .collect()
+}
+
+/**
+Ends every session in which the user with ID `user_id` is logged in.
+*/
+pub(crate) fn end_sessions_of_user(user_id: Uuid) {
+    if let Ok(mut states) = SESSION_STATES.lock() {
+        states.retain(|_, state| !state.is_of_user(user_id));
+    }
+}
+
+/**
+Number of unexpired sessions in the store.
+*/
+pub(crate) fn count() -> usize {
+    let Ok(states) = SESSION_STATES.lock() else {
+        return 0;
+    };
+    let now = Utc::now();
+    states.values().filter(|state| state.valid_until >= now).count()
+}
+
 pub(crate) struct State {
     session_state: HashMap<String, String>,
     valid_until: chrono::DateTime<Utc>,
 }
 
+impl State {
+    fn is_of_user(&self, user_id: Uuid) -> bool {
+        // the session stores the serialized ID of its user under "user_id"
+        self.session_state.get("user_id")
+            .and_then(|id| serde_json::from_str::<Uuid>(id).ok())
+            .is_some_and(|id| id == user_id)
+    }
+}
+
 /**
 Implementation of the [SessionStore] trait of [actix_session].
 */
 #[derive(Default)]
-pub(crate) struct MemorySession;
+pub struct MemorySession;
 
 impl SessionStore for MemorySession {
     async fn load(
@@ -75,8 +119,8 @@ impl SessionStore for MemorySession {
                 },
             );
 
-        Ok(SessionKey::try_from(session_key)
-            .map_err(|_| SaveError::Serialization(anyhow!("Invalid Session Key Error")))?)
+        SessionKey::try_from(session_key)
+            .map_err(|_| SaveError::Serialization(anyhow!("Invalid Session Key Error")))
     }
 
     async fn update(
@@ -0,0 +1,152 @@
+// normalization of user supplied names into URL slugs, and the slugs of posts with their history
+
+use anyhow::{anyhow, Result};
+use async_graphql::SimpleObject;
+use chrono::Utc;
+use sea_orm::prelude::*;
+use sea_orm::{sea_query::OnConflict, ActiveValue::Set, DatabaseTransaction};
+use unicode_normalization::UnicodeNormalization;
+
+use super::entity::{post, post_slug_history, user};
+
+const MAX_POST_SLUG_LENGTH: usize = 80;
+
+/**
+Slug of a tag name: NFKC folded and lowercased, with every run of other characters than letters and digits
+replaced by a single `-`. Fails when nothing is left.
+*/
+pub fn normalize_tag(name: &str) -> Result<String> {
+    let folded: String = name.nfkc().flat_map(char::to_lowercase).collect();
+    let slug = join_words(&folded, char::is_alphanumeric);
+    if slug.is_empty() {
+        return Err(anyhow!("tag `{}` has no letters or digits", name));
+    }
+    Ok(slug)
+}
+
+/**
+URL-safe slug of `text`: transliterated to lowercase ASCII, with every run of other characters than letters and
+digits replaced by a single `-`, and at most [MAX_POST_SLUG_LENGTH] long. Empty when `text` has no letters or digits.
+*/
+pub fn slugify(text: &str) -> String {
+    let ascii = deunicode::deunicode(text).to_lowercase();
+    let slug = join_words(&ascii, |c| c.is_ascii_alphanumeric());
+    if slug.len() <= MAX_POST_SLUG_LENGTH {
+        return slug;
+    }
+    // ASCII only, so any index is a char boundary
+    slug[..MAX_POST_SLUG_LENGTH].trim_end_matches('-').to_string()
+}
+
+fn join_words(text: &str, is_word_char: impl Fn(char) -> bool) -> String {
+    text.split(|c: char| !is_word_char(c))
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/**
+Slug for the post `post_id` generated from its title, with a `-2`, `-3`, ... suffix when the plain one is taken.
+*/
+pub async fn generate_post_slug(trx: &DatabaseTransaction, post_id: Uuid, title: &str) -> Result<String> {
+    let base = match slugify(title) {
+        slug if slug.is_empty() => "post".to_string(),
+        slug => slug,
+    };
+    let mut candidate = base.clone();
+    let mut suffix = 1;
+    while is_taken(trx, post_id, &candidate).await? {
+        suffix += 1;
+        candidate = format!("{}-{}", base, suffix);
+    }
+    Ok(candidate)
+}
+
+/**
+Slugifies a slug chosen for the post `post_id`, failing when another post uses it or used it before.
+*/
+pub async fn check_post_slug(trx: &DatabaseTransaction, post_id: Uuid, slug: &str) -> Result<String> {
+    let slug = slugify(slug);
+    if slug.is_empty() {
+        return Err(anyhow!("slug has no letters or digits"));
+    }
+    if is_taken(trx, post_id, &slug).await? {
+        return Err(anyhow!("slug `{}` is already taken", slug));
+    }
+    Ok(slug)
+}
+
+// old slugs stay reserved, so that they keep redirecting to their post
+async fn is_taken(trx: &DatabaseTransaction, post_id: Uuid, slug: &str) -> Result<bool> {
+    let post = post::Entity::find()
+        .filter(post::Column::Slug.eq(slug))
+        .filter(post::Column::Id.ne(post_id))
+        .one(trx).await?;
+    if post.is_some() {
+        return Ok(true);
+    }
+    let history = post_slug_history::Entity::find_by_id(slug.to_string())
+        .filter(post_slug_history::Column::PostId.ne(post_id))
+        .one(trx).await?;
+    Ok(history.is_some())
+}
+
+/**
+Keeps the old slug of a post in its history when the slug changes.
+*/
+pub async fn record_change(trx: &DatabaseTransaction, post_id: Uuid, old: Option<&str>, new: Option<&str>) -> Result<()> {
+    if old == new {
+        return Ok(());
+    }
+    if let Some(new) = new {
+        // the post is back at one of its old slugs
+        post_slug_history::Entity::delete_by_id(new.to_string()).exec(trx).await?;
+    }
+    if let Some(old) = old {
+        let history = post_slug_history::ActiveModel {
+            slug: Set(old.to_string()),
+            post_id: Set(post_id),
+            created_at: Set(Utc::now().naive_utc()),
+        };
+        post_slug_history::Entity::insert(history)
+            .on_conflict(
+                OnConflict::column(post_slug_history::Column::Slug)
+                    .update_columns([post_slug_history::Column::PostId, post_slug_history::Column::CreatedAt])
+                    .to_owned()
+            )
+            .exec_without_returning(trx)
+            .await?;
+    }
+    Ok(())
+}
+
+/**
+A post looked up by slug.
+*/
+#[derive(SimpleObject)]
+pub struct PostBySlug {
+    pub post: post::Model,
+    /// The current slug of the post, when it was looked up by a former one
+    pub redirect_to: Option<String>,
+}
+
+/**
+The post readable by `user` whose current or former slug is `slug`.
+*/
+pub async fn find_post(trx: &DatabaseTransaction, user: Option<&user::Model>, slug: &str) -> Result<Option<PostBySlug>> {
+    let post = post::Entity::find()
+        .filter(post::Column::Slug.eq(slug))
+        .filter(post::visible_to(user))
+        .one(trx).await?;
+    if let Some(post) = post {
+        return Ok(Some(PostBySlug { post, redirect_to: None }));
+    }
+
+    let Some(history) = post_slug_history::Entity::find_by_id(slug.to_string()).one(trx).await? else {
+        return Ok(None);
+    };
+    let post = post::Entity::find_by_id(history.post_id)
+        .filter(post::visible_to(user))
+        .one(trx).await?;
+    Ok(post.map(|post| PostBySlug { redirect_to: post.slug.clone(), post }))
+}
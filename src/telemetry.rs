@@ -0,0 +1,105 @@
+// tracing of HTTP requests, GraphQL operations and SQL queries, logged and exported over OpenTelemetry
+
+use anyhow::Result;
+use actix_web::{body::MessageBody, dev::{ServiceRequest, ServiceResponse}};
+use opentelemetry::{trace::TracerProvider as _, KeyValue};
+use opentelemetry_sdk::{runtime, trace::{self as sdktrace, TracerProvider}, Resource};
+use opentelemetry_otlp::WithExportConfig;
+use tracing::Span;
+use tracing_actix_web::{root_span, DefaultRootSpanBuilder, RootSpanBuilder, TracingLogger};
+use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+use uuid::Uuid;
+
+// SeaORM opens a span per statement at the trace level
+const DEFAULT_FILTER: &str = "info,sea_orm::database=trace";
+
+/**
+Where logs and spans go, configurable from the command line. The filter is `RUST_LOG`, as with `env_logger`.
+*/
+#[derive(Debug, Clone, Default, clap::Args)]
+pub struct TelemetryConfig {
+    /// Export spans over OTLP/gRPC to this endpoint, e.g. http://localhost:4317
+    #[clap(long, global = true, env = "OTEL_EXPORTER_OTLP_ENDPOINT")]
+    pub otlp_endpoint: Option<String>,
+    /// Print finished spans to stdout as JSON, for local use
+    #[clap(long, global = true)]
+    pub trace_stdout: bool,
+    /// Log lines as JSON objects, with the fields of their spans
+    #[clap(long, global = true)]
+    pub log_json: bool,
+}
+
+/**
+Flushes the exported spans when dropped, so keep it until the process exits.
+*/
+pub struct TelemetryGuard;
+
+impl Drop for TelemetryGuard {
+    fn drop(&mut self) {
+        opentelemetry::global::shutdown_tracer_provider();
+    }
+}
+
+/**
+Installs the global subscriber, which logs to stderr and exports spans as configured.
+*/
+pub fn init(config: &TelemetryConfig) -> Result<TelemetryGuard> {
+    let mut provider = TracerProvider::builder()
+        .with_config(sdktrace::config().with_resource(Resource::new(vec![
+            KeyValue::new("service.name", env!("CARGO_PKG_NAME")),
+        ])));
+    if let Some(endpoint) = &config.otlp_endpoint {
+        let exporter = opentelemetry_otlp::SpanExporterBuilder::from(
+            opentelemetry_otlp::new_exporter().tonic().with_endpoint(endpoint),
+        ).build_span_exporter()?;
+        provider = provider.with_batch_exporter(exporter, runtime::Tokio);
+    }
+    if config.trace_stdout {
+        provider = provider.with_simple_exporter(opentelemetry_stdout::SpanExporter::default());
+    }
+    let provider = provider.build();
+    let tracer = provider.tracer(env!("CARGO_PKG_NAME"));
+    opentelemetry::global::set_tracer_provider(provider);
+
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_FILTER));
+    // text lines show the fields of their spans too, so both carry the request and user IDs
+    let (text, json) = if config.log_json {
+        (None, Some(fmt::layer().json().with_current_span(false).with_span_list(true).with_writer(std::io::stderr)))
+    } else {
+        (Some(fmt::layer().with_writer(std::io::stderr)), None)
+    };
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(text)
+        .with(json)
+        .with(tracing_opentelemetry::layer().with_tracer(tracer))
+        .try_init()?;
+    Ok(TelemetryGuard)
+}
+
+/**
+Middleware opening the span of each request, with its generated `request_id`.
+*/
+pub type RequestTracing = TracingLogger<RequestSpan>;
+
+/**
+The root span of a request as by [DefaultRootSpanBuilder], with a `user_id` once the user is known.
+*/
+pub struct RequestSpan;
+
+impl RootSpanBuilder for RequestSpan {
+    fn on_request_start(request: &ServiceRequest) -> Span {
+        root_span!(request, user_id = tracing::field::Empty)
+    }
+
+    fn on_request_end<B: MessageBody>(span: Span, outcome: &Result<ServiceResponse<B>, actix_web::Error>) {
+        DefaultRootSpanBuilder::on_request_end(span, outcome);
+    }
+}
+
+/**
+Records the user of the current request on its root span.
+*/
+pub(crate) fn record_user(user_id: Uuid) {
+    Span::current().record("user_id", tracing::field::display(user_id));
+}
@@ -0,0 +1,134 @@
+// API tokens for clients which cannot run the passkey ceremonies, sent as bearer tokens and stored hashed
+
+use std::sync::Arc;
+use anyhow::{anyhow, Result};
+use async_graphql::{
+    async_trait,
+    extensions::{Extension, ExtensionContext, ExtensionFactory, NextResolve, ResolveInfo},
+    ServerError,
+    ServerResult,
+    SimpleObject,
+    Value,
+};
+use chrono::NaiveDateTime;
+use rand::distributions::{Alphanumeric, DistString};
+use sea_orm::{prelude::*, ActiveValue::Set, ConnectionTrait};
+use sha2::{Digest, Sha256};
+
+use super::entity::{api_token::{self, ApiScope}, user};
+
+// marks the tokens of this app, so that secret scanners can recognize them
+const TOKEN_PREFIX: &str = "lgp_";
+
+/**
+Request data with the scopes of the API token a GraphQL request is authenticated with.
+Requests authenticated by a session have none, and may call every mutation.
+*/
+pub struct TokenScopes(pub Vec<ApiScope>);
+
+/**
+A new API token with its secret, which is only shown this once.
+*/
+#[derive(SimpleObject)]
+pub struct CreatedApiToken {
+    /// The bearer token for the Authorization header
+    pub token: String,
+    pub api_token: api_token::Model,
+}
+
+fn hash(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+/**
+Creates an API token of the user `user_id`. The tokens are random enough for a plain hash to keep them secret.
+*/
+pub(crate) async fn create(
+    conn: &impl ConnectionTrait,
+    user_id: Uuid,
+    name: String,
+    scopes: Vec<ApiScope>,
+    expires_at: Option<NaiveDateTime>,
+) -> Result<CreatedApiToken> {
+    let now = chrono::Utc::now().naive_utc();
+    if name.trim().is_empty() {
+        return Err(anyhow!("token name must not be empty"));
+    }
+    if expires_at.is_some_and(|expires_at| expires_at <= now) {
+        return Err(anyhow!("expiry time must be in the future"));
+    }
+
+    let token = format!("{}{}", TOKEN_PREFIX, Alphanumeric.sample_string(&mut rand::thread_rng(), 40));
+    let api_token = api_token::ActiveModel {
+        id: Set(Uuid::new_v4()),
+        user_id: Set(user_id),
+        name: Set(name.trim().to_string()),
+        token_hash: Set(hash(&token)),
+        scopes: Set(serde_json::to_value(scopes)?),
+        expires_at: Set(expires_at),
+        created_at: Set(now),
+    };
+    let api_token = api_token.insert(conn).await?;
+    Ok(CreatedApiToken { token, api_token })
+}
+
+/**
+The user of the unexpired API token `token`, with the scopes of the token.
+*/
+pub(crate) async fn authenticate(conn: &impl ConnectionTrait, token: &str) -> Result<Option<(user::Model, TokenScopes)>> {
+    let Some(api_token) = api_token::Entity::find()
+        .filter(api_token::Column::TokenHash.eq(hash(token)))
+        .one(conn).await? else {
+        return Ok(None);
+    };
+    if api_token.expires_at.is_some_and(|expires_at| expires_at <= chrono::Utc::now().naive_utc()) {
+        return Ok(None);
+    }
+    let user = user::Entity::find_by_id(api_token.user_id).one(conn).await?;
+    Ok(user.map(|user| (user, TokenScopes(api_token.scope_list()))))
+}
+
+// the scope a mutation needs, none for the mutations which are for sessions only, like those of the tokens themselves
+fn required_scope(mutation: &str) -> Option<ApiScope> {
+    match mutation {
+        "createPost" | "updatePost" | "deletePost" | "restorePostRevision" | "setPostTags"
+            | "publishPost" | "unpublishPost" | "schedulePost" | "archivePost" => Some(ApiScope::Posts),
+        "addComment" | "editComment" | "deleteComment" | "hideComment" | "unhideComment" => Some(ApiScope::Comments),
+        "setUserRole" => Some(ApiScope::Users),
+        _ => None,
+    }
+}
+
+/**
+Extension factory which rejects the mutations outside of the [TokenScopes] of a request with "forbidden".
+Mutations missing from the list of scopes are rejected too, so a new mutation is for sessions only until it gets a scope.
+*/
+pub struct ScopeCheck;
+
+impl ExtensionFactory for ScopeCheck {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(ScopeCheckExtension)
+    }
+}
+
+struct ScopeCheckExtension;
+
+#[async_trait::async_trait]
+impl Extension for ScopeCheckExtension {
+    async fn resolve(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        info: ResolveInfo<'_>,
+        next: NextResolve<'_>,
+    ) -> ServerResult<Option<Value>> {
+        let root_mutation = info.path_node.parent.is_none() && info.parent_type == "Mutation";
+        if let (true, Some(scopes)) = (root_mutation, ctx.data_opt::<TokenScopes>()) {
+            let allowed = info.name.starts_with("__")
+                || required_scope(info.name).is_some_and(|scope| scopes.0.contains(&scope));
+            if !allowed {
+                return Err(ServerError::new("forbidden", None));
+            }
+        }
+        next.run(ctx, info).await
+    }
+}
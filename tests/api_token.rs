@@ -0,0 +1,135 @@
+use actix_web::http::{header, StatusCode};
+use sea_orm::{prelude::*, ActiveValue::Set};
+use serde_json::{json, Value};
+
+use learning_graphql::entity::{api_token, sea_orm_active_enums::Role};
+
+mod common;
+
+use common::{error_messages, TestApp, TestClient};
+
+const CREATE_TOKEN: &str = "mutation($scopes: [ApiScope!]!) { createApiToken(name: \"CI\", scopes: $scopes) { token apiToken { id scopes } } }";
+const CREATE_POST: &str = "mutation { createPost(title: \"From CI\", content: \"Text\") { id } }";
+
+// creates a token with `scopes` in the session of `client`, returning the token and its ID
+async fn create_token(client: &mut TestClient<'_>, scopes: Value) -> (String, String) {
+    let res = client.graphql(CREATE_TOKEN, json!({ "scopes": scopes })).await;
+    assert_eq!(error_messages(&res), Vec::<String>::new());
+    let created = &res["data"]["createApiToken"];
+    assert_eq!(created["apiToken"]["scopes"], scopes);
+    (created["token"].as_str().expect("token").to_string(), created["apiToken"]["id"].as_str().expect("ID").to_string())
+}
+
+// POSTs a GraphQL request with `token` as bearer token and without a session
+async fn graphql_with_token(app: &TestApp, token: &str, query: &str) -> (StatusCode, Value) {
+    let mut res = app.client().request(actix_web::http::Method::POST, "/graphql")
+        .bearer_auth(token)
+        .send_json(&json!({ "query": query })).await
+        .expect("request is sent");
+    let body = res.body().await.expect("response body is read");
+    (res.status(), serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+#[actix_web::test]
+async fn tokens_authenticate_as_their_user() {
+    let app = TestApp::start().await;
+    let (mut author, author_id) = app.user(Role::Author).await;
+    let (token, _) = create_token(&mut author, json!(["POSTS"])).await;
+
+    let (status, res) = graphql_with_token(&app, &token, CREATE_POST).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(error_messages(&res), Vec::<String>::new());
+
+    // only the hash is stored
+    let stored = api_token::Entity::find().all(&app.conn).await.expect("tokens are loaded");
+    assert_eq!(stored.len(), 1);
+    assert_eq!(stored[0].user_id, author_id);
+    assert_ne!(stored[0].token_hash, token);
+}
+
+#[actix_web::test]
+async fn tokens_are_listed_without_their_secret() {
+    let app = TestApp::start().await;
+    // the only user, so that every listed user is the admin themselves
+    let (mut admin, _) = app.user(Role::Admin).await;
+    create_token(&mut admin, json!(["USERS"])).await;
+
+    let res = admin.graphql("{ users { apiTokens { name scopes expiresAt } } }", json!({})).await;
+    assert_eq!(error_messages(&res), Vec::<String>::new());
+    assert_eq!(res["data"]["users"], json!([{ "apiTokens": [{ "name": "CI", "scopes": ["USERS"], "expiresAt": null }] }]));
+
+    let res = admin.graphql("{ users { apiTokens { token } } }", json!({})).await;
+    let errors = error_messages(&res);
+    assert!(errors.iter().any(|err| err.starts_with("Unknown field \"token\"")), "unexpected errors: {:?}", errors);
+}
+
+#[actix_web::test]
+async fn scopes_restrict_mutations() {
+    let app = TestApp::start().await;
+    let (mut author, _) = app.user(Role::Author).await;
+    let (token, _) = create_token(&mut author, json!(["COMMENTS"])).await;
+
+    let (status, res) = graphql_with_token(&app, &token, CREATE_POST).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(error_messages(&res), vec!["forbidden"]);
+
+    // queries need no scope
+    let (_, res) = graphql_with_token(&app, &token, "{ posts { nodes { id } } }").await;
+    assert_eq!(error_messages(&res), Vec::<String>::new());
+    assert_eq!(res["data"]["posts"]["nodes"], json!([]));
+
+    // a token cannot make itself more tokens, whatever its scopes
+    let (token, _) = create_token(&mut author, json!(["POSTS", "COMMENTS", "USERS"])).await;
+    let more_tokens = "mutation { createApiToken(name: \"More\", scopes: [POSTS]) { token } }";
+    let (_, res) = graphql_with_token(&app, &token, more_tokens).await;
+    assert_eq!(error_messages(&res), vec!["forbidden"]);
+
+    // nor does a scope lift the role of its user
+    let (_, res) = graphql_with_token(&app, &token, "mutation { setUserRole(userId: \"nobody\", role: ADMIN) { id } }").await;
+    assert_eq!(error_messages(&res), vec!["forbidden"]);
+}
+
+#[actix_web::test]
+async fn invalid_tokens_are_rejected() {
+    let app = TestApp::start().await;
+    let (mut author, _) = app.user(Role::Author).await;
+    let (token, token_id) = create_token(&mut author, json!(["POSTS"])).await;
+
+    let mut res = app.client().request(actix_web::http::Method::POST, "/graphql")
+        .bearer_auth("lgp_guessed")
+        .send_json(&json!({ "query": "{ hello }" })).await
+        .expect("request is sent");
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(res.headers().get(header::WWW_AUTHENTICATE).and_then(|value| value.to_str().ok()), Some("Bearer"));
+    res.body().await.expect("response body is read");
+
+    let expired = api_token::ActiveModel {
+        id: Set(token_id.parse().expect("token ID is a UUID")),
+        expires_at: Set(Some(chrono::Utc::now().naive_utc() - chrono::Duration::minutes(1))),
+        ..Default::default()
+    };
+    expired.update(&app.conn).await.expect("token exists");
+    let (status, _) = graphql_with_token(&app, &token, "{ hello }").await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (token, token_id) = create_token(&mut author, json!(["POSTS"])).await;
+    let res = author.graphql("mutation($id: String!) { revokeApiToken(id: $id) { name } }", json!({ "id": token_id })).await;
+    assert_eq!(res["data"]["revokeApiToken"], json!({ "name": "CI" }));
+    let (status, _) = graphql_with_token(&app, &token, "{ hello }").await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(api_token::Entity::find().count(&app.conn).await.expect("tokens are counted"), 1);
+}
+
+#[actix_web::test]
+async fn tokens_are_revoked_by_their_user_only() {
+    let app = TestApp::start().await;
+    let (mut author, _) = app.user(Role::Author).await;
+    let (_, token_id) = create_token(&mut author, json!(["POSTS"])).await;
+
+    let (mut admin, _) = app.user(Role::Admin).await;
+    let res = admin.graphql("mutation($id: String!) { revokeApiToken(id: $id) { name } }", json!({ "id": token_id })).await;
+    assert_eq!(error_messages(&res), vec!["token not found"]);
+
+    let res = app.client().graphql(CREATE_TOKEN, json!({ "scopes": ["POSTS"] })).await;
+    assert_eq!(error_messages(&res), vec!["unauthenticated"]);
+}
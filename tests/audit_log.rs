@@ -0,0 +1,128 @@
+use actix_web::http::StatusCode;
+use serde_json::{json, Value};
+
+use learning_graphql::entity::sea_orm_active_enums::Role;
+
+mod common;
+
+use common::{error_messages, TestApp, TestClient};
+
+const AUDIT_LOG: &str = "{ auditLog(first: 100) { nodes { event actor { id } subjectId detail } } }";
+
+// (event, subject ID) of the entries, sorted, as the entries of one transaction may share their time
+async fn audit_events(admin: &mut TestClient<'_>) -> Vec<(String, Value)> {
+    let res = admin.graphql(AUDIT_LOG, json!({})).await;
+    assert_eq!(error_messages(&res), Vec::<String>::new());
+    let mut events: Vec<(String, Value)> = res["data"]["auditLog"]["nodes"].as_array().expect("entries").iter()
+        .map(|entry| (entry["event"].as_str().expect("event").to_string(), entry["subjectId"].clone()))
+        .collect();
+    events.sort_by_key(|(event, subject_id)| (event.clone(), subject_id.to_string()));
+    events
+}
+
+#[actix_web::test]
+async fn security_events_are_audited() {
+    let app = TestApp::start().await;
+    let (mut admin, admin_id) = app.user(Role::Admin).await;
+    let (_, author_id) = app.user(Role::Author).await;
+
+    // a passkey's answer to its own challenge, replayed in the login of another user
+    let mut intruder = app.client();
+    let intruder_id = intruder.register().await;
+    let credential = intruder.authentication_response(intruder_id).await.expect("intruder answers own challenge");
+    let (status, _) = intruder.post("/auth/auth/start", &json!(author_id)).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = intruder.post("/auth/auth/finish", &credential).await;
+    assert_ne!(status, StatusCode::OK);
+
+    let res = admin.graphql(
+        "mutation($userId: String!) { setUserRole(userId: $userId, role: READER) { id } }",
+        json!({ "userId": author_id }),
+    ).await;
+    assert_eq!(error_messages(&res), Vec::<String>::new());
+
+    let admin_id = json!(admin_id.to_string());
+    let author_id = json!(author_id.to_string());
+    let intruder_id = json!(intruder_id.to_string());
+    let mut expected: Vec<(String, Value)> = [
+        ("REGISTRATION", &admin_id),
+        ("PASSKEY_ADDED", &admin_id),
+        ("REGISTRATION", &author_id),
+        ("PASSKEY_ADDED", &author_id),
+        ("REGISTRATION", &intruder_id),
+        ("PASSKEY_ADDED", &intruder_id),
+        ("LOGIN_FAILED", &author_id),
+        ("ROLE_CHANGED", &author_id),
+    ].into_iter().map(|(event, subject_id)| (event.to_string(), subject_id.clone())).collect();
+    expected.sort_by_key(|(event, subject_id)| (event.clone(), subject_id.to_string()));
+    assert_eq!(audit_events(&mut admin).await, expected);
+
+    let res = admin.graphql("{ auditLog(event: ROLE_CHANGED) { nodes { actor { id } detail } } }", json!({})).await;
+    assert_eq!(res["data"]["auditLog"]["nodes"], json!([{ "actor": { "id": admin_id }, "detail": "author -> reader" }]));
+}
+
+#[actix_web::test]
+async fn passkey_revocations_and_post_deletions_are_audited() {
+    let app = TestApp::start().await;
+    let (mut admin, admin_id) = app.user(Role::Admin).await;
+    let (mut author, author_id) = app.user(Role::Author).await;
+    let (mut other, _) = app.user(Role::Author).await;
+
+    let res = author.graphql("mutation { createPost(title: \"Doomed\", content: \"Text\") { id } }", json!({})).await;
+    let post_id = res["data"]["createPost"]["id"].as_str().expect("post is created").to_string();
+    let delete_post = "mutation($id: String!) { deletePost(id: $id) { title } }";
+    let res = other.graphql(delete_post, json!({ "id": post_id })).await;
+    assert_eq!(error_messages(&res), vec!["forbidden"]);
+    let res = author.graphql(delete_post, json!({ "id": post_id })).await;
+    assert_eq!(res["data"]["deletePost"], json!({ "title": "Doomed" }));
+    let res = author.graphql(delete_post, json!({ "id": post_id })).await;
+    assert_eq!(error_messages(&res), vec!["post not found"]);
+
+    let revoke_passkey = "mutation($userId: String!) { revokePasskey(userId: $userId) { credentialId } }";
+    let res = other.graphql(revoke_passkey, json!({ "userId": author_id })).await;
+    assert_eq!(error_messages(&res), vec!["forbidden"]);
+    let res = admin.graphql(revoke_passkey, json!({ "userId": author_id })).await;
+    assert_eq!(error_messages(&res), Vec::<String>::new());
+    let credential_id = res["data"]["revokePasskey"]["credentialId"].clone();
+    assert_ne!(author.login(author_id).await, StatusCode::OK);
+
+    let res = admin.graphql("{ auditLog(event: POST_DELETED) { nodes { actor { id } subjectId detail } } }", json!({})).await;
+    assert_eq!(res["data"]["auditLog"]["nodes"], json!([{ "actor": { "id": author_id.to_string() }, "subjectId": post_id, "detail": "Doomed" }]));
+    let res = admin.graphql("{ auditLog(event: PASSKEY_REVOKED) { nodes { actor { id } subjectId detail } } }", json!({})).await;
+    assert_eq!(
+        res["data"]["auditLog"]["nodes"],
+        json!([{ "actor": { "id": admin_id.to_string() }, "subjectId": author_id.to_string(), "detail": credential_id }]),
+    );
+}
+
+#[actix_web::test]
+async fn audit_log_is_for_admins_only() {
+    let app = TestApp::start().await;
+
+    let res = app.client().graphql(AUDIT_LOG, json!({})).await;
+    assert_eq!(error_messages(&res), vec!["unauthenticated"]);
+
+    let (mut author, _) = app.user(Role::Author).await;
+    let res = author.graphql(AUDIT_LOG, json!({})).await;
+    assert_eq!(error_messages(&res), vec!["forbidden"]);
+}
+
+#[actix_web::test]
+async fn access_log_has_a_line_per_request() {
+    let app = TestApp::start().await;
+    let (mut author, author_id) = app.user(Role::Author).await;
+    author.graphql("query Greeting { hello }", json!({})).await;
+
+    let access_log = app.access_log();
+    let line = access_log.last().expect("the request is logged");
+    assert_eq!(line["method"], "POST");
+    assert_eq!(line["path"], "/graphql");
+    assert_eq!(line["status"], 200);
+    assert_eq!(line["user_id"], author_id.to_string());
+    assert_eq!(line["operation_name"], "Greeting");
+    assert!(line["latency_ms"].is_number());
+    assert!(line["request_id"].is_string());
+
+    // the finished registration is logged with the new user
+    assert!(access_log.iter().any(|line| line["path"] == "/auth/register/finish" && line["user_id"].is_string()));
+}
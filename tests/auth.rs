@@ -0,0 +1,133 @@
+use actix_web::http::{Method, StatusCode};
+use serde_json::{json, Value};
+
+use learning_graphql::entity::sea_orm_active_enums::Role;
+
+mod common;
+
+use common::{error_messages, TestApp};
+
+const CREATE_POST: &str = "mutation { createPost(title: \"Hello\", content: \"World\") { title slug } }";
+
+#[actix_web::test]
+async fn registration_logs_in_as_new_reader() {
+    let app = TestApp::start().await;
+
+    let mut anonymous = app.client();
+    let res = anonymous.graphql(CREATE_POST, json!({})).await;
+    assert_eq!(error_messages(&res), vec!["unauthenticated"]);
+
+    let mut reader = app.client();
+    reader.register().await;
+    let res = reader.graphql(CREATE_POST, json!({})).await;
+    assert_eq!(error_messages(&res), vec!["forbidden"]);
+}
+
+#[actix_web::test]
+async fn login_with_registered_passkey() {
+    let app = TestApp::start().await;
+    let mut client = app.client();
+    let user_id = client.register().await;
+    app.set_role(user_id, Role::Author).await;
+
+    assert_eq!(client.login(user_id).await, StatusCode::OK);
+    let res = client.graphql(CREATE_POST, json!({})).await;
+    assert_eq!(error_messages(&res), Vec::<String>::new());
+    assert_eq!(res["data"]["createPost"], json!({ "title": "Hello", "slug": "hello" }));
+}
+
+#[actix_web::test]
+async fn login_without_passkey_fails() {
+    let app = TestApp::start().await;
+    let (_, user_id) = app.user(Role::Author).await;
+
+    // a new authenticator has no credential of the user
+    let mut intruder = app.client();
+    assert_ne!(intruder.login(user_id).await, StatusCode::OK);
+    let res = intruder.graphql(CREATE_POST, json!({})).await;
+    assert_eq!(error_messages(&res), vec!["unauthenticated"]);
+}
+
+#[actix_web::test]
+async fn login_renews_the_session() {
+    let app = TestApp::start().await;
+
+    // a session the attacker started, planted in the browser of the victim
+    let mut attacker = app.client();
+    let (status, _) = attacker.post("/auth/register/start", &Value::Null).await;
+    assert_eq!(status, StatusCode::OK);
+    let planted = attacker.session.clone().expect("session cookie is set");
+
+    let mut victim = app.client();
+    victim.session = Some(planted.clone());
+    victim.register().await;
+    assert_ne!(victim.session.as_ref().map(|session| session.value()), Some(planted.value()));
+
+    let res = attacker.graphql(CREATE_POST, json!({})).await;
+    assert_eq!(error_messages(&res), vec!["unauthenticated"]);
+}
+
+#[actix_web::test]
+async fn role_changes_apply_to_open_sessions() {
+    let app = TestApp::start().await;
+    let (mut author, author_id) = app.user(Role::Author).await;
+    let res = author.graphql(CREATE_POST, json!({})).await;
+    assert_eq!(error_messages(&res), Vec::<String>::new());
+
+    app.set_role(author_id, Role::Reader).await;
+    let res = author.graphql(CREATE_POST, json!({})).await;
+    assert_eq!(error_messages(&res), vec!["forbidden"]);
+}
+
+#[actix_web::test]
+async fn demoted_authors_cannot_change_their_posts() {
+    let app = TestApp::start().await;
+    let (mut author, author_id) = app.user(Role::Author).await;
+    let res = author.graphql("mutation { createPost(title: \"Mine\", content: \"Text\") { id } }", json!({})).await;
+    let id = res["data"]["createPost"]["id"].as_str().expect("post is created").to_string();
+
+    app.set_role(author_id, Role::Reader).await;
+    let mutations = [
+        "mutation($id: String!) { updatePost(id: $id, title: \"Changed\") { id } }",
+        "mutation($id: String!) { restorePostRevision(postId: $id, revisionId: $id) { id } }",
+        "mutation($id: String!) { setPostTags(postId: $id, tags: [\"rust\"]) { id } }",
+        "mutation($id: String!) { publishPost(id: $id) { id } }",
+        "mutation($id: String!) { unpublishPost(id: $id) { id } }",
+        "mutation($id: String!) { schedulePost(id: $id, at: \"2999-01-01T00:00:00\") { id } }",
+        "mutation($id: String!) { archivePost(id: $id) { id } }",
+        "mutation($id: String!) { deletePost(id: $id) { id } }",
+    ];
+    for mutation in mutations {
+        let res = author.graphql(mutation, json!({ "id": id })).await;
+        assert_eq!(error_messages(&res), vec!["forbidden"], "{}", mutation);
+    }
+}
+
+#[actix_web::test]
+async fn revoked_passkeys_end_every_login() {
+    let app = TestApp::start().await;
+    let (mut admin, _) = app.user(Role::Admin).await;
+    let (mut author, author_id) = app.user(Role::Author).await;
+    let res = author.graphql("mutation { createApiToken(name: \"CI\", scopes: [POSTS]) { token } }", json!({})).await;
+    let token = res["data"]["createApiToken"]["token"].as_str().expect("token is created").to_string();
+
+    let res = admin.graphql(
+        "mutation($userId: String!) { revokePasskey(userId: $userId) { credentialId } }",
+        json!({ "userId": author_id }),
+    ).await;
+    assert_eq!(error_messages(&res), Vec::<String>::new());
+
+    // the session of the author is gone, as are their token and passkey
+    let res = author.graphql(CREATE_POST, json!({})).await;
+    assert_eq!(error_messages(&res), vec!["unauthenticated"]);
+    let res = app.client().request(Method::POST, "/graphql")
+        .bearer_auth(&token)
+        .send_json(&json!({ "query": CREATE_POST })).await
+        .expect("request is sent");
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    assert_ne!(author.login(author_id).await, StatusCode::OK);
+
+    // other users stay logged in
+    let res = admin.graphql("{ users { id } }", json!({})).await;
+    assert_eq!(error_messages(&res), Vec::<String>::new());
+}
@@ -0,0 +1,125 @@
+use serde_json::{json, Value};
+
+use learning_graphql::entity::sea_orm_active_enums::Role;
+
+mod common;
+
+use common::{error_messages, TestApp, TestClient};
+
+// a published post of `author`, returning its ID
+async fn published_post(author: &mut TestClient<'_>) -> String {
+    let res = author.graphql("mutation { createPost(title: \"Post\", content: \"Text\") { id } }", json!({})).await;
+    let id = res["data"]["createPost"]["id"].as_str().expect("post is created").to_string();
+    let res = author.graphql("mutation($id: String!) { publishPost(id: $id) { status } }", json!({ "id": id })).await;
+    assert_eq!(res["data"]["publishPost"]["status"], "PUBLISHED");
+    id
+}
+
+async fn add_comment(client: &mut TestClient<'_>, post_id: &str, body: &str) -> String {
+    let res = client.graphql(
+        "mutation($postId: String!, $body: String!) { addComment(postId: $postId, body: $body) { id } }",
+        json!({ "postId": post_id, "body": body }),
+    ).await;
+    assert_eq!(error_messages(&res), Vec::<String>::new());
+    res["data"]["addComment"]["id"].as_str().expect("comment is added").to_string()
+}
+
+// runs `mutation` on the comment `id`, returning the error messages
+async fn on_comment(client: &mut TestClient<'_>, mutation: &str, id: &str) -> Vec<String> {
+    error_messages(&client.graphql(mutation, json!({ "id": id })).await)
+}
+
+// the bodies of the comments on the only post, as `client` sees them
+async fn comment_bodies(client: &mut TestClient<'_>) -> Value {
+    let res = client.graphql("{ posts { nodes { comments { nodes { body } } } } }", json!({})).await;
+    assert_eq!(error_messages(&res), Vec::<String>::new());
+    res["data"]["posts"]["nodes"][0]["comments"]["nodes"].clone()
+}
+
+const EDIT: &str = "mutation($id: String!) { editComment(id: $id, body: \"Edited\") { body } }";
+const DELETE: &str = "mutation($id: String!) { deleteComment(id: $id) { status } }";
+const HIDE: &str = "mutation($id: String!) { hideComment(id: $id) { status } }";
+
+#[actix_web::test]
+async fn only_commenters_change_their_comments() {
+    let app = TestApp::start().await;
+    let (mut author, _) = app.user(Role::Author).await;
+    let (mut commenter, _) = app.user(Role::Reader).await;
+    let (mut other, _) = app.user(Role::Reader).await;
+    let (mut admin, _) = app.user(Role::Admin).await;
+    let post_id = published_post(&mut author).await;
+    let comment_id = add_comment(&mut commenter, &post_id, "Original").await;
+
+    for client in [&mut author, &mut other, &mut admin] {
+        assert_eq!(on_comment(client, EDIT, &comment_id).await, vec!["forbidden"]);
+        assert_eq!(on_comment(client, DELETE, &comment_id).await, vec!["forbidden"]);
+    }
+    assert_eq!(on_comment(&mut app.client(), EDIT, &comment_id).await, vec!["unauthenticated"]);
+    assert_eq!(on_comment(&mut app.client(), DELETE, &comment_id).await, vec!["unauthenticated"]);
+    assert_eq!(comment_bodies(&mut other).await, json!([{ "body": "Original" }]));
+
+    assert_eq!(on_comment(&mut commenter, EDIT, &comment_id).await, Vec::<String>::new());
+    assert_eq!(comment_bodies(&mut other).await, json!([{ "body": "Edited" }]));
+    assert_eq!(on_comment(&mut commenter, DELETE, &comment_id).await, Vec::<String>::new());
+    assert_eq!(comment_bodies(&mut other).await, json!([{ "body": null }]));
+}
+
+#[actix_web::test]
+async fn only_post_authors_hide_comments() {
+    let app = TestApp::start().await;
+    let (mut author, _) = app.user(Role::Author).await;
+    let (mut other_author, _) = app.user(Role::Author).await;
+    let (mut commenter, _) = app.user(Role::Reader).await;
+    let post_id = published_post(&mut author).await;
+    let comment_id = add_comment(&mut commenter, &post_id, "Spam").await;
+
+    assert_eq!(on_comment(&mut other_author, HIDE, &comment_id).await, vec!["forbidden"]);
+    assert_eq!(on_comment(&mut commenter, HIDE, &comment_id).await, vec!["forbidden"]);
+    assert_eq!(on_comment(&mut app.client(), HIDE, &comment_id).await, vec!["unauthenticated"]);
+    assert_eq!(comment_bodies(&mut app.client()).await, json!([{ "body": "Spam" }]));
+
+    assert_eq!(on_comment(&mut author, HIDE, &comment_id).await, Vec::<String>::new());
+    assert_eq!(comment_bodies(&mut app.client()).await, json!([]));
+}
+
+#[actix_web::test]
+async fn anonymous_readers_cannot_comment() {
+    let app = TestApp::start().await;
+    let (mut author, _) = app.user(Role::Author).await;
+    let post_id = published_post(&mut author).await;
+
+    let res = app.client().graphql(
+        "mutation($postId: String!) { addComment(postId: $postId, body: \"Anonymous\") { id } }",
+        json!({ "postId": post_id }),
+    ).await;
+    assert_eq!(error_messages(&res), vec!["unauthenticated"]);
+    assert_eq!(comment_bodies(&mut author).await, json!([]));
+}
+
+#[actix_web::test]
+async fn hidden_comments_are_left_out() {
+    let app = TestApp::start().await;
+    let (mut author, _) = app.user(Role::Author).await;
+    let (mut commenter, _) = app.user(Role::Reader).await;
+    let (mut other, _) = app.user(Role::Reader).await;
+    let post_id = published_post(&mut author).await;
+    let hidden_id = add_comment(&mut commenter, &post_id, "Hidden").await;
+    add_comment(&mut other, &post_id, "Shown").await;
+    let res = other.graphql(
+        "mutation($postId: String!, $parentId: String!) { addComment(postId: $postId, body: \"Hidden reply\", parentId: $parentId) { id } }",
+        json!({ "postId": post_id, "parentId": hidden_id }),
+    ).await;
+    let reply_id = res["data"]["addComment"]["id"].as_str().expect("reply is added").to_string();
+    assert_eq!(on_comment(&mut author, HIDE, &hidden_id).await, Vec::<String>::new());
+    assert_eq!(on_comment(&mut author, HIDE, &reply_id).await, Vec::<String>::new());
+
+    assert_eq!(comment_bodies(&mut app.client()).await, json!([{ "body": "Shown" }]));
+    assert_eq!(comment_bodies(&mut other).await, json!([{ "body": "Shown" }]));
+    // the comment author and the post author still see the hidden comment
+    assert_eq!(comment_bodies(&mut commenter).await, json!([{ "body": "Hidden" }, { "body": "Shown" }]));
+    let res = author.graphql("{ posts { nodes { comments { nodes { body replies { body } } } } } }", json!({})).await;
+    assert_eq!(res["data"]["posts"]["nodes"][0]["comments"]["nodes"], json!([
+        { "body": "Hidden", "replies": [{ "body": "Hidden reply" }] },
+        { "body": "Shown", "replies": [] },
+    ]));
+}
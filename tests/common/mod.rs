@@ -0,0 +1,301 @@
+// harness of the end-to-end tests, which drive the app over HTTP against an in-memory SQLite database with the migrations applied
+
+// every test crate uses its own part of the harness
+#![allow(dead_code)]
+
+use std::{io::{self, Write}, num::NonZeroUsize, sync::{Arc, Mutex}};
+use actix_web::{cookie::{self, Cookie}, http::{header, Method, StatusCode}, web, App};
+use migration::{Migrator, MigratorTrait};
+use sea_orm::prelude::*;
+use sea_orm::{ActiveValue::Set, ConnectOptions, Database};
+use serde_json::{json, Value};
+use url::Url;
+use webauthn_authenticator_rs::{softtoken::SoftToken, WebauthnAuthenticator};
+use webauthn_rs::prelude::{CreationChallengeResponse, RequestChallengeResponse};
+
+use learning_graphql::{
+    access_log::AccessLog,
+    configure,
+    csrf::CsrfConfig,
+    session_middleware,
+    AppConfig,
+    AppState,
+    entity::{sea_orm_active_enums::Role, user},
+    limit::QueryLimits,
+    metrics::{MetricsConfig, RequestMetrics},
+    telemetry::RequestTracing,
+    persisted::{CacheKind, PersistedQueryConfig},
+    rate_limit::RateLimitConfig,
+};
+
+pub const ORIGIN: &str = "http://localhost:8080";
+pub const METRICS_TOKEN: &str = "metrics-token";
+
+/**
+Configuration of a test app, with the default limits of the command line, introspection enabled and metrics behind [METRICS_TOKEN].
+*/
+pub fn config() -> AppConfig {
+    AppConfig {
+        rp_id: "localhost".to_string(),
+        rp_origin: Url::parse(ORIGIN).expect("origin is a valid URL"),
+        limits: QueryLimits {
+            limit_depth: 10,
+            limit_complexity: 5000,
+            limit_recursive_depth: 32,
+            limit_aliases: 30,
+        },
+        persisted_queries: PersistedQueryConfig {
+            apq_cache: CacheKind::Memory,
+            apq_cache_size: NonZeroUsize::new(16).expect("cache size is not zero"),
+            trusted_documents: None,
+        },
+        trusted_documents: None,
+        graphiql: false,
+        introspection: true,
+        metrics: MetricsConfig {
+            metrics_token: Some(METRICS_TOKEN.to_string()),
+            metrics_bind: None,
+        },
+        // every test client shares the loopback address, so the limits are out of reach unless a test lowers them
+        rate_limits: RateLimitConfig {
+            auth_ip_rate_per_minute: 6000.0,
+            auth_ip_burst: 1000,
+            auth_user_rate_per_minute: 6000.0,
+            auth_user_burst: 1000,
+            lockout_after: 1000,
+            lockout_secs: 30,
+            lockout_max_secs: 3600,
+        },
+        csrf: CsrfConfig::default(),
+    }
+}
+
+/**
+A running server with its own database.
+*/
+pub struct TestApp {
+    pub server: actix_test::TestServer,
+    pub conn: DatabaseConnection,
+    access_log: Arc<Mutex<Vec<u8>>>,
+}
+
+impl TestApp {
+    pub async fn start() -> Self {
+        Self::start_with(config()).await
+    }
+
+    pub async fn start_with(config: AppConfig) -> Self {
+        Self::serve(config, actix_test::config(), 1).await
+    }
+
+    /// Serves with `workers` server workers, which accept connections in turn
+    pub async fn start_with_workers(workers: usize) -> Self {
+        Self::serve(config(), actix_test::config().workers(workers), 1).await
+    }
+
+    /// Serves from a pool of `connections` database connections, for storages which open their own
+    /// transactions while the transaction of a request holds a connection
+    pub async fn start_with_connections(config: AppConfig, connections: u32) -> Self {
+        Self::serve(config, actix_test::config(), connections).await
+    }
+
+    async fn serve(config: AppConfig, server_config: actix_test::TestServerConfig, connections: u32) -> Self {
+        // a lone connection keeps a private in-memory database alive, and more connections share a named one
+        let url = if connections == 1 {
+            "sqlite::memory:".to_string()
+        } else {
+            format!("sqlite:file:{}?mode=memory&cache=shared", Uuid::new_v4())
+        };
+        let mut options = ConnectOptions::new(url);
+        options.max_connections(connections).min_connections(connections);
+        let conn = Database::connect(options).await.expect("in-memory database opens");
+        Migrator::up(&conn, None).await.expect("migrations apply");
+
+        // shared by the workers, as in the binary
+        let state = web::Data::new(AppState::new(&config, conn.clone()).expect("test origin is valid"));
+        let key = cookie::Key::generate();
+        let access_log = Arc::new(Mutex::new(Vec::new()));
+        let access_log_writer = AccessLog::to(SharedBuffer(access_log.clone()));
+        let server = actix_test::start_with(server_config, move || {
+            App::new()
+                .app_data(state.clone())
+                .wrap(access_log_writer.clone())
+                .wrap(session_middleware(key.clone()))
+                .wrap(RequestMetrics)
+                .wrap(RequestTracing::new())
+                .configure(configure)
+        });
+        Self { server, conn, access_log }
+    }
+
+    /// A client without a session and with a new software authenticator
+    pub fn client(&self) -> TestClient<'_> {
+        TestClient {
+            server: &self.server,
+            client: awc::Client::new(),
+            session: None,
+            // unlike the soft passkey, the soft token sets the user verified flag, which logins require
+            authenticator: WebauthnAuthenticator::new(SoftToken::new().expect("software authenticator is created").0),
+        }
+    }
+
+    /// Registers a user with `role` through a new client, which is logged in as that user
+    pub async fn user(&self, role: Role) -> (TestClient<'_>, Uuid) {
+        let mut client = self.client();
+        let user_id = client.register().await;
+        if role != Role::Reader {
+            self.set_role(user_id, role).await;
+        }
+        (client, user_id)
+    }
+
+    /// GETs /metrics with `token` as bearer token, returning the status and the body
+    pub async fn metrics(&self, token: Option<&str>) -> (StatusCode, String) {
+        let mut req = self.server.get("/metrics");
+        if let Some(token) = token {
+            req = req.bearer_auth(token);
+        }
+        let mut res = req.send().await.expect("request is sent");
+        let body = res.body().await.expect("response body is read");
+        (res.status(), String::from_utf8_lossy(&body).into_owned())
+    }
+
+    /// The lines of the access log so far, parsed as JSON
+    pub fn access_log(&self) -> Vec<Value> {
+        let access_log = self.access_log.lock().expect("access log is not poisoned");
+        String::from_utf8_lossy(&access_log).lines()
+            .map(|line| serde_json::from_str(line).expect("access log lines are JSON"))
+            .collect()
+    }
+
+    pub async fn set_role(&self, user_id: Uuid, role: Role) {
+        let user = user::ActiveModel {
+            id: Set(user_id),
+            role: Set(role),
+            ..Default::default()
+        };
+        user.update(&self.conn).await.expect("user exists");
+    }
+}
+
+// the access log of a test app, shared with the test
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().map_err(|_| io::Error::other("access log is poisoned"))?.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/**
+A client of a [TestApp], which keeps its session cookie and passkey across requests.
+*/
+pub struct TestClient<'a> {
+    server: &'a actix_test::TestServer,
+    client: awc::Client,
+    /// The session cookie, as last set by the server
+    pub session: Option<Cookie<'static>>,
+    authenticator: WebauthnAuthenticator<SoftToken>,
+}
+
+impl TestClient<'_> {
+    /// Drops the open connections, so that the next request is accepted anew by the next server worker
+    pub fn reconnect(&mut self) {
+        self.client = awc::Client::new();
+    }
+
+    /// A request with the session cookie and nothing else, for requests the helpers would not send
+    pub fn request(&self, method: Method, path: &str) -> awc::ClientRequest {
+        let mut req = self.client.request(method, self.server.url(path));
+        if let Some(session) = &self.session {
+            req = req.cookie(session.clone());
+        }
+        req
+    }
+
+    /// POSTs `body` as JSON from a page of the app, and returns the status with the body parsed as JSON, or null when empty
+    pub async fn post(&mut self, path: &str, body: &Value) -> (StatusCode, Value) {
+        let req = self.request(Method::POST, path).insert_header((header::ORIGIN, ORIGIN));
+        let mut res = req.send_json(body).await.expect("request is sent");
+        if let Some(session) = res.cookie("sess_id") {
+            self.session = Some(session);
+        }
+
+        let body = res.body().await.expect("response body is read");
+        let body = if body.is_empty() {
+            Value::Null
+        } else {
+            serde_json::from_slice(&body).unwrap_or_else(|_| Value::String(String::from_utf8_lossy(&body).into_owned()))
+        };
+        (res.status(), body)
+    }
+
+    pub async fn graphql(&mut self, query: &str, variables: Value) -> Value {
+        let (status, body) = self.post("/graphql", &json!({ "query": query, "variables": variables })).await;
+        assert_eq!(status, StatusCode::OK, "unexpected response: {}", body);
+        body
+    }
+
+    /// Runs the registration ceremony with a new passkey, which logs the client in as the new user
+    pub async fn register(&mut self) -> Uuid {
+        let (status, challenge) = self.post("/auth/register/start", &Value::Null).await;
+        assert_eq!(status, StatusCode::OK, "unexpected response: {}", challenge);
+        let challenge: CreationChallengeResponse = serde_json::from_value(challenge).expect("registration challenge");
+        let user_id = Uuid::from_slice(&challenge.public_key.user.id.0).expect("user handle is a UUID");
+
+        let credential = self.authenticator
+            .do_registration(Url::parse(ORIGIN).expect("origin is a valid URL"), challenge)
+            .expect("software authenticator registers");
+        let credential = serde_json::to_value(credential).expect("credential serializes");
+        let (status, body) = self.post("/auth/register/finish", &credential).await;
+        assert_eq!(status, StatusCode::OK, "unexpected response: {}", body);
+        user_id
+    }
+
+    /// Runs the authentication ceremony with the passkey of an earlier registration, returning the final status
+    pub async fn login(&mut self, user_id: Uuid) -> StatusCode {
+        let credential = match self.authentication_response(user_id).await {
+            Ok(credential) => credential,
+            Err(status) => return status,
+        };
+        let (status, _) = self.post("/auth/auth/finish", &credential).await;
+        status
+    }
+
+    /// Starts the authentication ceremony and answers its challenge with the passkey of an earlier registration,
+    /// without finishing it. Fails with the status of the start, or 401 when the authenticator has no passkey of the user.
+    pub async fn authentication_response(&mut self, user_id: Uuid) -> Result<Value, StatusCode> {
+        let (status, challenge) = self.post("/auth/auth/start", &json!(user_id)).await;
+        if status != StatusCode::OK {
+            return Err(status);
+        }
+        let challenge: RequestChallengeResponse = serde_json::from_value(challenge).expect("authentication challenge");
+
+        let credential = self.authenticator
+            .do_authentication(Url::parse(ORIGIN).expect("origin is a valid URL"), challenge)
+            .map_err(|_| StatusCode::UNAUTHORIZED)?;
+        Ok(serde_json::to_value(credential).expect("credential serializes"))
+    }
+}
+
+/**
+Messages of the errors in a GraphQL response.
+*/
+pub fn error_messages(res: &Value) -> Vec<String> {
+    res["errors"].as_array().into_iter().flatten()
+        .filter_map(|err| err["message"].as_str().map(str::to_string))
+        .collect()
+}
+
+/**
+Codes in the extensions of the errors in a GraphQL response.
+*/
+pub fn error_codes(res: &Value) -> Vec<String> {
+    res["errors"].as_array().into_iter().flatten()
+        .filter_map(|err| err["extensions"]["code"].as_str().map(str::to_string))
+        .collect()
+}
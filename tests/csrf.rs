@@ -0,0 +1,144 @@
+use actix_web::{cookie::SameSite, http::{header, Method, StatusCode}};
+use sea_orm::{EntityTrait, PaginatorTrait};
+use serde_json::{json, Value};
+
+use learning_graphql::entity::{post, sea_orm_active_enums::Role};
+
+mod common;
+
+use common::{TestApp, ORIGIN};
+
+const CREATE_POST: &str = "mutation { createPost(title: \"Forged\", content: \"Text\") { id } }";
+
+async fn post_count(app: &TestApp) -> u64 {
+    post::Entity::find().count(&app.conn).await.expect("posts are counted")
+}
+
+#[actix_web::test]
+async fn cross_origin_requests_are_forbidden() {
+    let app = TestApp::start().await;
+    let (author, _) = app.user(Role::Author).await;
+
+    let res = author.request(Method::POST, "/graphql")
+        .insert_header((header::ORIGIN, "http://evil.example"))
+        .send_json(&json!({ "query": CREATE_POST })).await
+        .expect("request is sent");
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+    // without an Origin, as from older browsers, the Referer tells the page
+    let res = author.request(Method::POST, "/graphql")
+        .insert_header((header::REFERER, "http://evil.example/page"))
+        .send_json(&json!({ "query": CREATE_POST })).await
+        .expect("request is sent");
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+    // the origin of sandboxed frames and data URLs
+    let res = author.request(Method::POST, "/graphql")
+        .insert_header((header::ORIGIN, "null"))
+        .send_json(&json!({ "query": CREATE_POST })).await
+        .expect("request is sent");
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+    let res = author.request(Method::POST, "/auth/register/start")
+        .insert_header((header::ORIGIN, "http://evil.example"))
+        .send_json(&Value::Null).await
+        .expect("request is sent");
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+    assert_eq!(post_count(&app).await, 0);
+}
+
+#[actix_web::test]
+async fn allowed_origins_are_configurable() {
+    let mut config = common::config();
+    config.csrf.allowed_origins = vec!["http://admin.localhost:3000".parse().expect("origin is a valid URL")];
+    let app = TestApp::start_with(config).await;
+    let (author, _) = app.user(Role::Author).await;
+
+    let res = author.request(Method::POST, "/graphql")
+        .insert_header((header::ORIGIN, "http://admin.localhost:3000"))
+        .send_json(&json!({ "query": CREATE_POST })).await
+        .expect("request is sent");
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(post_count(&app).await, 1);
+}
+
+#[actix_web::test]
+async fn simple_requests_need_the_token() {
+    let app = TestApp::start().await;
+    let (author, _) = app.user(Role::Author).await;
+    let body = json!({ "query": CREATE_POST }).to_string();
+
+    // a form of another site may send text/plain without a preflight, and from the same origin as far as headers go
+    let res = author.request(Method::POST, "/graphql")
+        .insert_header((header::ORIGIN, ORIGIN))
+        .content_type("text/plain")
+        .send_body(body.clone()).await
+        .expect("request is sent");
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+    let mut res = author.request(Method::GET, "/auth/csrf").send().await.expect("request is sent");
+    assert_eq!(res.status(), StatusCode::OK);
+    let cookie = res.cookie("csrf_token").expect("token cookie is set");
+    let token: Value = res.json().await.expect("token is JSON");
+    assert_eq!(token["token"].as_str(), Some(cookie.value()));
+
+    // the cookie alone is what a forged request would carry
+    let res = author.request(Method::POST, "/graphql")
+        .cookie(cookie.clone())
+        .content_type("text/plain")
+        .send_body(body.clone()).await
+        .expect("request is sent");
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+    let res = author.request(Method::POST, "/graphql")
+        .cookie(cookie.clone())
+        .insert_header(("x-csrf-token", "guessed"))
+        .content_type("text/plain")
+        .send_body(body.clone()).await
+        .expect("request is sent");
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    assert_eq!(post_count(&app).await, 0);
+
+    let res = author.request(Method::POST, "/graphql")
+        .cookie(cookie.clone())
+        .insert_header(("x-csrf-token", cookie.value()))
+        .content_type("text/plain")
+        .send_body(body).await
+        .expect("request is sent");
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(post_count(&app).await, 1);
+}
+
+#[actix_web::test]
+async fn session_cookie_is_same_site() {
+    let app = TestApp::start().await;
+    let res = app.client().request(Method::POST, "/auth/register/start")
+        .insert_header((header::ORIGIN, ORIGIN))
+        .send_json(&Value::Null).await
+        .expect("request is sent");
+    assert_eq!(res.status(), StatusCode::OK);
+    let session = res.cookie("sess_id").expect("session cookie is set");
+    assert_eq!(session.same_site(), Some(SameSite::Strict));
+    assert_eq!(session.http_only(), Some(true));
+}
+
+#[actix_web::test]
+async fn cross_origin_websockets_are_forbidden() {
+    let app = TestApp::start().await;
+
+    let res = awc::Client::new().ws(app.server.url("/graphql/ws"))
+        .origin("http://evil.example")
+        .protocols(["graphql-transport-ws"])
+        .connect().await;
+    assert!(
+        matches!(res, Err(awc::error::WsClientError::InvalidResponseStatus(StatusCode::FORBIDDEN))),
+        "unexpected handshake: {:?}", res.map(|(res, _)| res.status()),
+    );
+
+    let res = awc::Client::new().ws(app.server.url("/graphql/ws"))
+        .origin(ORIGIN)
+        .protocols(["graphql-transport-ws"])
+        .connect().await;
+    assert!(res.is_ok(), "same-origin handshake fails");
+}
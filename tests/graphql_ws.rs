@@ -0,0 +1,94 @@
+use awc::{error::WsProtocolError, ws::{Frame, Message}};
+use futures::{Sink, SinkExt, Stream, StreamExt};
+use serde_json::{json, Value};
+
+use learning_graphql::entity::sea_orm_active_enums::Role;
+
+mod common;
+
+use common::{error_messages, TestApp, TestClient, ORIGIN};
+
+// opens a graphql-transport-ws connection with the session of `client`, up to the acknowledged init
+async fn connect(
+    app: &TestApp,
+    client: &TestClient<'_>,
+) -> impl Sink<Message, Error = WsProtocolError> + Stream<Item = Result<Frame, WsProtocolError>> + Unpin {
+    let mut request = awc::Client::new().ws(app.server.url("/graphql/ws"))
+        .origin(ORIGIN)
+        .protocols(["graphql-transport-ws"]);
+    if let Some(session) = &client.session {
+        request = request.cookie(session.clone());
+    }
+    let (_, mut connection) = request.connect().await.expect("handshake succeeds");
+
+    let init = json!({ "type": "connection_init" });
+    connection.send(Message::Text(init.to_string().into())).await.expect("init is sent");
+    let ack = receive(&mut connection).await;
+    assert_eq!(ack["type"], "connection_ack", "unexpected message: {}", ack);
+    connection
+}
+
+// the next message of `connection`, skipping the completions of earlier operations
+async fn receive(connection: &mut (impl Stream<Item = Result<Frame, WsProtocolError>> + Unpin)) -> Value {
+    loop {
+        let frame = connection.next().await.expect("connection stays open").expect("frame is read");
+        let Frame::Text(text) = frame else {
+            continue;
+        };
+        let message: Value = serde_json::from_slice(&text).expect("message is JSON");
+        if message["type"] != "complete" {
+            return message;
+        }
+    }
+}
+
+// runs `query` as the operation `id` of `connection`, returning its response
+async fn operation(
+    connection: &mut (impl Sink<Message, Error = WsProtocolError> + Stream<Item = Result<Frame, WsProtocolError>> + Unpin),
+    id: &str,
+    query: &str,
+) -> Value {
+    let subscribe = json!({ "type": "subscribe", "id": id, "payload": { "query": query } });
+    connection.send(Message::Text(subscribe.to_string().into())).await.expect("subscribe is sent");
+    let message = receive(connection).await;
+    assert_eq!((&message["type"], &message["id"]), (&json!("next"), &json!(id)), "unexpected message: {}", message);
+    message["payload"].clone()
+}
+
+// runs `query` as the only operation of a connection with the session of `client`
+async fn ws_operation(app: &TestApp, client: &TestClient<'_>, query: &str) -> Value {
+    operation(&mut connect(app, client).await, "1", query).await
+}
+
+#[actix_web::test]
+async fn operations_run_over_websockets() {
+    let app = TestApp::start().await;
+    let (author, _) = app.user(Role::Author).await;
+
+    let res = ws_operation(&app, &author, "mutation { createPost(title: \"Live\", content: \"Text\") { title } }").await;
+    assert_eq!(error_messages(&res), Vec::<String>::new());
+    assert_eq!(res["data"]["createPost"], json!({ "title": "Live" }));
+
+    // committed, so that the next connection sees the draft of its author
+    let res = ws_operation(&app, &author, "{ posts { nodes { title } } }").await;
+    assert_eq!(error_messages(&res), Vec::<String>::new());
+    assert_eq!(res["data"]["posts"]["nodes"], json!([{ "title": "Live" }]));
+
+    let res = ws_operation(&app, &app.client(), "mutation { createPost(title: \"Anonymous\", content: \"Text\") { title } }").await;
+    assert_eq!(error_messages(&res), vec!["unauthenticated"]);
+}
+
+#[actix_web::test]
+async fn role_changes_apply_to_open_connections() {
+    let app = TestApp::start().await;
+    let (author, author_id) = app.user(Role::Author).await;
+    let mut connection = connect(&app, &author).await;
+
+    let create_post = "mutation { createPost(title: \"Live\", content: \"Text\") { title } }";
+    let res = operation(&mut connection, "1", create_post).await;
+    assert_eq!(error_messages(&res), Vec::<String>::new());
+
+    app.set_role(author_id, Role::Reader).await;
+    let res = operation(&mut connection, "2", create_post).await;
+    assert_eq!(error_messages(&res), vec!["forbidden"]);
+}
@@ -0,0 +1,101 @@
+use actix_web::http::{header, Method, StatusCode};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use url::form_urlencoded;
+
+use learning_graphql::entity::sea_orm_active_enums::Role;
+
+mod common;
+
+use common::{TestApp, TestClient};
+
+const POSTS: &str = "/graphql?query=%7B%20posts%20%7B%20nodes%20%7B%20id%20%7D%20%7D%20%7D";
+
+fn header_value(res: &awc::ClientResponse<impl futures::Stream>, name: header::HeaderName) -> Option<String> {
+    res.headers().get(name).and_then(|value| value.to_str().ok()).map(str::to_string)
+}
+
+// GETs `path` as `client`, returning the status, the ETag, the Cache-Control and the body
+async fn get(client: &TestClient<'_>, path: &str, if_none_match: Option<&str>) -> (StatusCode, Option<String>, Option<String>, Vec<u8>) {
+    let mut req = client.request(Method::GET, path);
+    if let Some(etag) = if_none_match {
+        req = req.insert_header((header::IF_NONE_MATCH, etag));
+    }
+    let mut res = req.send().await.expect("request is sent");
+    assert_eq!(header_value(&res, header::VARY).as_deref(), Some("Cookie, Authorization"));
+    let body = res.body().await.expect("response body is read").to_vec();
+    (res.status(), header_value(&res, header::ETAG), header_value(&res, header::CACHE_CONTROL), body)
+}
+
+#[actix_web::test]
+async fn get_responses_vary_by_viewer() {
+    let app = TestApp::start().await;
+
+    let mut res = app.client().request(Method::GET, "/graphql?query=%7B%20hello%20%7D")
+        .send().await
+        .expect("request is sent");
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers().get(header::VARY).and_then(|value| value.to_str().ok()), Some("Cookie, Authorization"));
+    res.body().await.expect("response body is read");
+
+    let res = app.client().request(Method::GET, "/graphql?query=mutation%20%7B%20hello%20%7D")
+        .send().await
+        .expect("request is sent");
+    assert_eq!(res.status(), StatusCode::METHOD_NOT_ALLOWED);
+    assert_eq!(res.headers().get(header::VARY).and_then(|value| value.to_str().ok()), Some("Cookie, Authorization"));
+}
+
+#[actix_web::test]
+async fn etags_are_the_hash_of_the_body() {
+    let app = TestApp::start().await;
+    let client = app.client();
+
+    let (status, etag, _, body) = get(&client, POSTS, None).await;
+    assert_eq!(status, StatusCode::OK);
+    let etag = etag.expect("ETag is set");
+    assert_eq!(etag, format!("\"{:x}\"", Sha256::digest(&body)));
+
+    let (status, _, _, body) = get(&client, POSTS, Some(&etag)).await;
+    assert_eq!(status, StatusCode::NOT_MODIFIED);
+    assert!(body.is_empty());
+
+    let (status, _, _, body) = get(&client, POSTS, Some("\"stale\"")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(!body.is_empty());
+}
+
+#[actix_web::test]
+async fn only_anonymous_responses_are_public() {
+    let app = TestApp::start().await;
+
+    let (_, _, cache_control, _) = get(&app.client(), POSTS, None).await;
+    assert_eq!(cache_control.as_deref(), Some("max-age=60"));
+
+    let (author, _) = app.user(Role::Author).await;
+    let (_, _, cache_control, _) = get(&author, POSTS, None).await;
+    assert_eq!(cache_control.as_deref(), Some("max-age=60, private"));
+}
+
+#[actix_web::test]
+async fn mutations_picked_by_operation_name_are_rejected() {
+    let app = TestApp::start().await;
+    let client = app.client();
+
+    // a mutation picked by name out of a document which has a query too
+    let document = "query Read { hello } mutation Write { createPost(title: \"T\", content: \"C\") { id } }";
+    let query = form_urlencoded::Serializer::new(String::new())
+        .append_pair("query", document)
+        .append_pair("operationName", "Write")
+        .finish();
+    let (status, _, _, body) = get(&client, &format!("/graphql?{}", query), None).await;
+    assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED, "{}", String::from_utf8_lossy(&body));
+    let body: serde_json::Value = serde_json::from_slice(&body).expect("body is JSON");
+    assert_eq!(body["errors"][0]["extensions"]["code"], json!("METHOD_NOT_ALLOWED"));
+
+    let query = form_urlencoded::Serializer::new(String::new())
+        .append_pair("query", document)
+        .append_pair("operationName", "Read")
+        .finish();
+    let (status, _, _, body) = get(&client, &format!("/graphql?{}", query), None).await;
+    assert_eq!(status, StatusCode::OK, "{}", String::from_utf8_lossy(&body));
+}
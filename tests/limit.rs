@@ -0,0 +1,97 @@
+use serde_json::{json, Value};
+
+use learning_graphql::limit::{CODE_RECURSION_TOO_DEEP, CODE_TOO_COMPLEX, CODE_TOO_DEEP, CODE_TOO_MANY_ALIASES};
+
+mod common;
+
+use common::{config, error_codes, TestApp};
+
+async fn app_with_limits(depth: usize, complexity: usize, recursive_depth: usize, aliases: usize) -> TestApp {
+    let mut config = config();
+    config.limits.limit_depth = depth;
+    config.limits.limit_complexity = complexity;
+    config.limits.limit_recursive_depth = recursive_depth;
+    config.limits.limit_aliases = aliases;
+    TestApp::start_with(config).await
+}
+
+// `count` aliased `hello` fields, each of complexity 1
+fn aliased_hellos(count: usize) -> String {
+    let fields: Vec<String> = (0..count).map(|index| format!("hello{}: hello", index)).collect();
+    format!("{{ {} }}", fields.join(" "))
+}
+
+// `count` nested inline fragments around `hello`
+fn nested_fragments(count: usize) -> String {
+    format!("{{ {}hello{} }}", "... on QueryRoot { ".repeat(count), " }".repeat(count))
+}
+
+async fn query(app: &TestApp, query: &str) -> Value {
+    app.client().graphql(query, json!({})).await
+}
+
+#[actix_web::test]
+async fn depth_limit() {
+    let app = app_with_limits(3, 1000, 32, 30).await;
+
+    let res = query(&app, "{ __schema { queryType { name } } }").await;
+    assert_eq!(error_codes(&res), Vec::<String>::new());
+
+    let res = query(&app, "{ __schema { queryType { fields { name } } } }").await;
+    assert_eq!(error_codes(&res), vec![CODE_TOO_DEEP]);
+}
+
+#[actix_web::test]
+async fn complexity_limit() {
+    let app = app_with_limits(10, 5, 32, 30).await;
+
+    let res = query(&app, &aliased_hellos(5)).await;
+    assert_eq!(error_codes(&res), Vec::<String>::new());
+
+    let res = query(&app, &aliased_hellos(6)).await;
+    assert_eq!(error_codes(&res), vec![CODE_TOO_COMPLEX]);
+}
+
+#[actix_web::test]
+async fn alias_limit() {
+    let app = app_with_limits(10, 1000, 32, 3).await;
+
+    let res = query(&app, &aliased_hellos(3)).await;
+    assert_eq!(error_codes(&res), Vec::<String>::new());
+
+    let res = query(&app, &aliased_hellos(4)).await;
+    assert_eq!(error_codes(&res), vec![CODE_TOO_MANY_ALIASES]);
+}
+
+#[actix_web::test]
+async fn recursive_depth_limit() {
+    let app = app_with_limits(10, 1000, 16, 30).await;
+
+    // each inline fragment nests one level deeper than its parent selection set
+    let res = query(&app, &nested_fragments(16)).await;
+    assert_eq!(error_codes(&res), Vec::<String>::new());
+
+    let res = query(&app, &nested_fragments(17)).await;
+    assert_eq!(error_codes(&res), vec![CODE_RECURSION_TOO_DEEP]);
+}
+
+#[actix_web::test]
+async fn list_fields_cost_more() {
+    // a connection costs its page size times its selection
+    let app = app_with_limits(10, 50, 32, 30).await;
+
+    let res = query(&app, "{ tags(first: 10) { nodes { slug } } }").await;
+    assert_eq!(error_codes(&res), Vec::<String>::new());
+
+    let res = query(&app, "{ tags(first: 100) { nodes { slug } } }").await;
+    assert_eq!(error_codes(&res), vec![CODE_TOO_COMPLEX]);
+}
+
+#[actix_web::test]
+async fn default_limits_allow_nested_connections() {
+    let app = TestApp::start().await;
+
+    // a default page of posts with a default page of comments each, and their authors
+    let res = query(&app, "{ posts { nodes { title comments { nodes { body author { id slug name } } } } } }").await;
+    assert_eq!(error_codes(&res), Vec::<String>::new());
+}
@@ -0,0 +1,95 @@
+use serde_json::{json, Value};
+
+use learning_graphql::entity::sea_orm_active_enums::Role;
+
+mod common;
+
+use common::{error_messages, TestApp, TestClient};
+
+// creates a post with `content`, returning its slug
+async fn create_post(author: &mut TestClient<'_>, content: &str) -> String {
+    let res = author.graphql(
+        "mutation($content: String!) { createPost(title: \"Markdown\", content: $content) { slug } }",
+        json!({ "content": content }),
+    ).await;
+    assert_eq!(error_messages(&res), Vec::<String>::new());
+    res["data"]["createPost"]["slug"].as_str().expect("post is created").to_string()
+}
+
+// the post of `slug` with `fields`, as read by its author
+async fn read_post(author: &mut TestClient<'_>, slug: &str, fields: &str) -> Value {
+    let query = format!("query($slug: String!) {{ post(slug: $slug) {{ post {{ {} }} }} }}", fields);
+    let res = author.graphql(&query, json!({ "slug": slug })).await;
+    assert_eq!(error_messages(&res), Vec::<String>::new());
+    res["data"]["post"]["post"].clone()
+}
+
+#[actix_web::test]
+async fn html_has_no_scripts() {
+    let app = TestApp::start().await;
+    let (mut author, _) = app.user(Role::Author).await;
+    let content = r#"<script>alert("script")</script>
+
+<img src="cat.png" onerror="alert('onerror')">
+
+<a href="javascript:alert('href')" onclick="alert('onclick')">inline link</a> and [markdown link](javascript:alert('markdown'))
+"#;
+    let slug = create_post(&mut author, content).await;
+
+    let html = read_post(&mut author, &slug, "html").await["html"].as_str().expect("html").to_string();
+    for removed in ["<script", "alert(", "onerror", "onclick", "javascript:"] {
+        assert!(!html.contains(removed), "`{}` is left in {}", removed, html);
+    }
+    assert!(html.contains("<img src=\"cat.png\""), "{}", html);
+    assert!(html.contains("inline link</a>"), "{}", html);
+    assert!(html.contains("markdown link</a>"), "{}", html);
+}
+
+#[actix_web::test]
+async fn fenced_code_is_highlighted() {
+    let app = TestApp::start().await;
+    let (mut author, _) = app.user(Role::Author).await;
+    let slug = create_post(&mut author, "```rust\nfn main() {}\n```\n\n```unknown-language\n<b>as is</b>\n```\n").await;
+
+    let html = read_post(&mut author, &slug, "html").await["html"].as_str().expect("html").to_string();
+    assert!(html.contains("<pre><code class=\"language-rust\"><span class=\""), "{}", html);
+    assert!(html.contains("<span class=\"storage type function rust\">fn</span>"), "{}", html);
+    // code in other languages is escaped, not highlighted
+    assert!(html.contains("&lt;b&gt;as is&lt;/b&gt;"), "{}", html);
+}
+
+#[actix_web::test]
+async fn excerpts_cut_between_characters() {
+    let app = TestApp::start().await;
+    let (mut author, _) = app.user(Role::Author).await;
+    let slug = create_post(&mut author, "**Café** crème brûlée\n\n日本語のテキスト").await;
+
+    let post = read_post(&mut author, &slug, "full: excerpt short: excerpt(length: 9) word: excerpt(length: 3)").await;
+    assert_eq!(post, json!({
+        "full": "Café crème brûlée 日本語のテキスト",
+        "short": "Café…",
+        "word": "Caf…",
+    }));
+
+    let slug = create_post(&mut author, "日本語のテキスト").await;
+    let post = read_post(&mut author, &slug, "excerpt(length: 3)").await;
+    assert_eq!(post["excerpt"], json!("日本語…"));
+}
+
+#[actix_web::test]
+async fn html_follows_updates() {
+    let app = TestApp::start().await;
+    let (mut author, _) = app.user(Role::Author).await;
+    let res = author.graphql("mutation { createPost(title: \"Cached\", content: \"*before*\") { id slug html } }", json!({})).await;
+    let post = &res["data"]["createPost"];
+    assert_eq!(post["html"], json!("<p><em>before</em></p>\n"));
+    let slug = post["slug"].as_str().expect("post is created").to_string();
+    assert_eq!(read_post(&mut author, &slug, "html").await["html"], json!("<p><em>before</em></p>\n"));
+
+    let res = author.graphql(
+        "mutation($id: String!) { updatePost(id: $id, content: \"**after**\") { html } }",
+        json!({ "id": post["id"] }),
+    ).await;
+    assert_eq!(res["data"]["updatePost"]["html"], json!("<p><strong>after</strong></p>\n"));
+    assert_eq!(read_post(&mut author, &slug, "html").await["html"], json!("<p><strong>after</strong></p>\n"));
+}
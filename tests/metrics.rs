@@ -0,0 +1,77 @@
+use actix_web::http::StatusCode;
+use serde_json::json;
+
+use learning_graphql::entity::sea_orm_active_enums::Role;
+
+mod common;
+
+use common::{TestApp, METRICS_TOKEN};
+
+// value of the sample of `name` with all of `labels`, given as `key="value"`
+fn sample(metrics: &str, name: &str, labels: &[&str]) -> Option<f64> {
+    metrics.lines()
+        .filter(|line| line.starts_with(&format!("{}{{", name)) || line.starts_with(&format!("{} ", name)))
+        .find(|line| labels.iter().all(|label| line.contains(label)))
+        .and_then(|line| line.rsplit(' ').next())
+        .and_then(|value| value.parse().ok())
+}
+
+#[actix_web::test]
+async fn metrics_require_the_token() {
+    let app = TestApp::start().await;
+
+    assert_eq!(app.metrics(None).await.0, StatusCode::UNAUTHORIZED);
+    assert_eq!(app.metrics(Some("guess")).await.0, StatusCode::UNAUTHORIZED);
+    assert_eq!(app.metrics(Some(METRICS_TOKEN)).await.0, StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn empty_tokens_are_no_tokens() {
+    for token in ["", " "] {
+        let mut config = common::config();
+        config.metrics.metrics_token = Some(token.to_string());
+        let app = TestApp::start_with(config).await;
+
+        assert_eq!(app.metrics(None).await.0, StatusCode::NOT_FOUND);
+        assert_eq!(app.metrics(Some("")).await.0, StatusCode::NOT_FOUND);
+        assert_eq!(app.metrics(Some(token)).await.0, StatusCode::NOT_FOUND);
+    }
+}
+
+#[actix_web::test]
+async fn metrics_count_requests_operations_and_ceremonies() {
+    let app = TestApp::start().await;
+    let (mut author, author_id) = app.user(Role::Author).await;
+    assert_eq!(author.login(author_id).await, StatusCode::OK);
+    author.graphql("query Greeting { hello }", json!({})).await;
+    author.graphql("mutation { setUserRole(userId: \"nobody\", role: ADMIN) { id } }", json!({})).await;
+
+    let (status, metrics) = app.metrics(Some(METRICS_TOKEN)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let graphql_requests = sample(&metrics, "http_requests_total", &["method=\"POST\"", "route=\"/graphql\"", "status=\"200\""]);
+    assert_eq!(graphql_requests, Some(2.0));
+    let greetings = sample(&metrics, "graphql_operations_total", &["operation_name=\"Greeting\"", "operation_type=\"query\""]);
+    assert_eq!(greetings, Some(1.0));
+    assert_eq!(sample(&metrics, "graphql_errors_total", &["code=\"none\""]), Some(1.0));
+    assert_eq!(sample(&metrics, "db_transactions_total", &["outcome=\"commit\""]), Some(1.0));
+    assert_eq!(sample(&metrics, "db_transactions_total", &["outcome=\"rollback\""]), Some(1.0));
+
+    // registration, then a login with the new passkey
+    let registrations = sample(&metrics, "webauthn_ceremonies_total", &["ceremony=\"registration\"", "stage=\"finish\"", "outcome=\"success\""]);
+    assert_eq!(registrations, Some(1.0));
+    let logins = sample(&metrics, "webauthn_ceremonies_total", &["ceremony=\"authentication\"", "stage=\"finish\"", "outcome=\"success\""]);
+    assert_eq!(logins, Some(1.0));
+    assert!(sample(&metrics, "sessions", &[]).is_some_and(|sessions| sessions >= 1.0));
+}
+
+#[actix_web::test]
+async fn long_operation_names_share_a_label() {
+    let app = TestApp::start().await;
+    let long_name = "G".repeat(100);
+    app.client().graphql(&format!("query {} {{ hello }}", long_name), json!({})).await;
+
+    let (_, metrics) = app.metrics(Some(METRICS_TOKEN)).await;
+    assert_eq!(sample(&metrics, "graphql_operations_total", &["operation_name=\"other\"", "operation_type=\"query\""]), Some(1.0));
+    assert!(!metrics.contains(&long_name));
+}
@@ -0,0 +1,101 @@
+use std::{env, fs};
+use actix_web::http::StatusCode;
+use sea_orm::prelude::*;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+use learning_graphql::{
+    entity::persisted_query,
+    persisted::{self, CacheKind, TrustedDocuments},
+};
+
+mod common;
+
+use common::{error_codes, error_messages, TestApp, TestClient};
+
+const QUERY: &str = "{ hello }";
+
+fn hash(query: &str) -> String {
+    format!("{:x}", Sha256::digest(query.as_bytes()))
+}
+
+// POSTs a persisted query request, with the document only if `query` is given
+async fn persisted_query(client: &mut TestClient<'_>, query: Option<&str>, hash: &str) -> Value {
+    let mut body = json!({ "extensions": { "persistedQuery": { "version": 1, "sha256Hash": hash } } });
+    if let Some(query) = query {
+        body["query"] = json!(query);
+    }
+    let (status, body) = client.post("/graphql", &body).await;
+    assert_eq!(status, StatusCode::OK, "unexpected response: {}", body);
+    body
+}
+
+async fn apq_round_trip(apq_cache: CacheKind) {
+    let mut config = common::config();
+    config.persisted_queries.apq_cache = apq_cache;
+    let app = TestApp::start_with_connections(config, 2).await;
+    let mut client = app.client();
+
+    let res = persisted_query(&mut client, None, &hash(QUERY)).await;
+    assert_eq!(error_messages(&res), vec!["PersistedQueryNotFound"]);
+
+    let res = persisted_query(&mut client, Some(QUERY), &hash(QUERY)).await;
+    assert_eq!(error_messages(&res), Vec::<String>::new());
+
+    let res = persisted_query(&mut client, None, &hash(QUERY)).await;
+    assert_eq!(error_messages(&res), Vec::<String>::new());
+    assert_eq!(res["data"]["hello"], json!("Hello, graphql!"));
+
+    let stored = persisted_query::Entity::find_by_id(hash(QUERY)).one(&app.conn).await.expect("query runs");
+    assert_eq!(stored.is_some(), matches!(apq_cache, CacheKind::Sqlite));
+}
+
+#[actix_web::test]
+async fn apq_round_trip_in_memory() {
+    apq_round_trip(CacheKind::Memory).await;
+}
+
+#[actix_web::test]
+async fn apq_round_trip_in_sqlite() {
+    apq_round_trip(CacheKind::Sqlite).await;
+}
+
+// an app which trusts the documents of `queries` only
+async fn app_trusting(queries: &[&str]) -> TestApp {
+    let path = env::temp_dir().join(format!("trusted-documents-{}.txt", Uuid::new_v4()));
+    let content = queries.iter().map(|query| hash(query)).collect::<Vec<_>>().join("\n");
+    fs::write(&path, format!("# trusted documents\n{}\n", content)).expect("allowlist is written");
+    let trusted_documents = TrustedDocuments::load(&path).expect("allowlist is read");
+    fs::remove_file(&path).expect("allowlist is removed");
+
+    let mut config = common::config();
+    config.trusted_documents = Some(trusted_documents);
+    TestApp::start_with(config).await
+}
+
+#[actix_web::test]
+async fn trusted_documents_reject_other_documents() {
+    let app = app_trusting(&[QUERY]).await;
+    let mut client = app.client();
+
+    let res = client.graphql("{ hello hello2: hello }", json!({})).await;
+    assert_eq!(error_codes(&res), vec![persisted::CODE_NOT_ALLOWED]);
+    let res = persisted_query(&mut client, None, &hash("{ posts { nodes { id } } }")).await;
+    assert_eq!(error_codes(&res), vec![persisted::CODE_NOT_ALLOWED]);
+}
+
+#[actix_web::test]
+async fn trusted_documents_accept_allowlisted_hashes() {
+    let app = app_trusting(&[QUERY]).await;
+    let mut client = app.client();
+
+    let res = client.graphql(QUERY, json!({})).await;
+    assert_eq!(res["data"]["hello"], json!("Hello, graphql!"));
+
+    // the hash alone is enough once the document is registered
+    let res = persisted_query(&mut client, Some(QUERY), &hash(QUERY)).await;
+    assert_eq!(error_messages(&res), Vec::<String>::new());
+    let res = persisted_query(&mut client, None, &hash(QUERY)).await;
+    assert_eq!(error_messages(&res), Vec::<String>::new());
+    assert_eq!(res["data"]["hello"], json!("Hello, graphql!"));
+}
@@ -0,0 +1,151 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use sea_orm::prelude::*;
+use sea_orm::ActiveValue::Set;
+use serde_json::{json, Value};
+
+use learning_graphql::entity::{post, sea_orm_active_enums::Role, user};
+
+mod common;
+
+use common::{error_messages, TestApp, TestClient};
+
+fn at(month: u32, day: u32) -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(2024, month, day).and_then(|date| date.and_hms_opt(12, 0, 0)).expect("valid date")
+}
+
+/**
+Published posts by two authors, where the first author has the slug `alice`:
+
+| post | author | title  | created | updated | slug |
+|------|--------|--------|---------|---------|------|
+| 0    | alice  | Banana | Jan 1   | Feb 9   | yes  |
+| 1    | alice  | apple  | Jan 2   | Feb 8   | yes  |
+| 2    | other  | Cherry | Jan 3   | Feb 7   | yes  |
+| 3    | other  | apple  | Jan 4   | Feb 6   | yes  |
+| 4    | alice  | Date   | Jan 5   | Feb 5   | no   |
+
+Returns the post IDs in this order, and the ID of the other author.
+*/
+async fn create_posts(app: &TestApp) -> (Vec<String>, Uuid) {
+    let (mut alice, alice_id) = app.user(Role::Author).await;
+    let (mut other, other_id) = app.user(Role::Author).await;
+    user::ActiveModel { id: Set(alice_id), slug: Set(Some("alice".to_string())), ..Default::default() }
+        .update(&app.conn).await.expect("user exists");
+
+    let posts = [(true, "Banana"), (true, "apple"), (false, "Cherry"), (false, "apple"), (true, "Date")];
+    let mut ids = Vec::new();
+    for (index, (by_alice, title)) in posts.into_iter().enumerate() {
+        let author = if by_alice { &mut alice } else { &mut other };
+        let res = author.graphql(
+            "mutation($title: String!) { createPost(title: $title, content: \"Text\") { id } }",
+            json!({ "title": title }),
+        ).await;
+        let id = res["data"]["createPost"]["id"].as_str().expect("post is created").to_string();
+        let res = author.graphql("mutation($id: String!) { publishPost(id: $id) { status } }", json!({ "id": id })).await;
+        assert_eq!(res["data"]["publishPost"]["status"], "PUBLISHED");
+        if index == 4 {
+            let res = author.graphql("mutation($id: String!) { updatePost(id: $id, slug: null) { slug } }", json!({ "id": id })).await;
+            assert_eq!(res["data"]["updatePost"]["slug"], Value::Null);
+        }
+
+        let day = index as u32 + 1;
+        post::ActiveModel {
+            id: Set(Uuid::parse_str(&id).expect("post ID is a UUID")),
+            created_at: Set(at(1, day)),
+            updated_at: Set(at(2, 10 - day)),
+            ..Default::default()
+        }.update(&app.conn).await.expect("post exists");
+        ids.push(id);
+    }
+    (ids, other_id)
+}
+
+// the indexes in `ids` of the posts listed with `arguments`, and the end cursor
+async fn list(client: &mut TestClient<'_>, ids: &[String], arguments: &str) -> (Vec<usize>, Value) {
+    let query = format!("{{ posts{} {{ nodes {{ id }} pageInfo {{ endCursor }} }} }}", arguments);
+    let res = client.graphql(&query, json!({})).await;
+    assert_eq!(error_messages(&res), Vec::<String>::new(), "{}", arguments);
+    let posts = &res["data"]["posts"];
+    let indexes = posts["nodes"].as_array().expect("nodes").iter()
+        .map(|node| ids.iter().position(|id| node["id"] == json!(id)).expect("post is one of the created ones"))
+        .collect();
+    (indexes, posts["pageInfo"]["endCursor"].clone())
+}
+
+#[actix_web::test]
+async fn every_filter_applies() {
+    let app = TestApp::start().await;
+    let (ids, other_id) = create_posts(&app).await;
+    let mut reader = app.client();
+
+    let filters = [
+        (format!("authorId: \"{}\"", other_id), vec![3, 2]),
+        ("authorSlug: \"alice\"".to_string(), vec![4, 1, 0]),
+        ("authorSlug: \"nobody\"".to_string(), vec![]),
+        ("createdAfter: \"2024-01-03T12:00:00\"".to_string(), vec![4, 3, 2]),
+        ("createdBefore: \"2024-01-03T12:00:00\"".to_string(), vec![1, 0]),
+        ("updatedAfter: \"2024-02-08T12:00:00\"".to_string(), vec![1, 0]),
+        ("updatedBefore: \"2024-02-07T12:00:00\"".to_string(), vec![4, 3]),
+        ("createdAfter: \"2024-01-02T00:00:00\", updatedAfter: \"2024-02-07T00:00:00\"".to_string(), vec![2, 1]),
+        ("titleContains: \"APP\"".to_string(), vec![3, 1]),
+        ("titleContains: \"%\"".to_string(), vec![]),
+        ("hasSlug: true".to_string(), vec![3, 2, 1, 0]),
+        ("hasSlug: false".to_string(), vec![4]),
+    ];
+    for (filter, expected) in filters {
+        let (indexes, _) = list(&mut reader, &ids, &format!("(filter: {{ {} }})", filter)).await;
+        assert_eq!(indexes, expected, "{}", filter);
+    }
+}
+
+#[actix_web::test]
+async fn every_order_applies() {
+    let app = TestApp::start().await;
+    let (ids, _) = create_posts(&app).await;
+    let mut reader = app.client();
+
+    // the two posts titled apple are ordered by ID
+    let (first_apple, second_apple) = if ids[1] < ids[3] { (1, 3) } else { (3, 1) };
+    let orders = [
+        ("", vec![4, 3, 2, 1, 0]),
+        ("(order: { field: CREATED_AT, direction: ASC })", vec![0, 1, 2, 3, 4]),
+        ("(order: { field: CREATED_AT, direction: DESC })", vec![4, 3, 2, 1, 0]),
+        ("(order: { field: UPDATED_AT, direction: ASC })", vec![4, 3, 2, 1, 0]),
+        ("(order: { field: UPDATED_AT, direction: DESC })", vec![0, 1, 2, 3, 4]),
+        // by code point, where capitals come first
+        ("(order: { field: TITLE, direction: ASC })", vec![0, 2, 4, first_apple, second_apple]),
+        ("(order: { field: TITLE, direction: DESC })", vec![second_apple, first_apple, 4, 2, 0]),
+        ("(order: { field: TITLE })", vec![second_apple, first_apple, 4, 2, 0]),
+    ];
+    for (order, expected) in orders {
+        let (indexes, _) = list(&mut reader, &ids, order).await;
+        assert_eq!(indexes, expected, "{}", order);
+    }
+}
+
+#[actix_web::test]
+async fn pages_follow_the_order() {
+    let app = TestApp::start().await;
+    let (ids, _) = create_posts(&app).await;
+    let mut reader = app.client();
+    let order = "order: { field: TITLE, direction: ASC }";
+    let (all, _) = list(&mut reader, &ids, &format!("({})", order)).await;
+
+    // the page boundary falls between the posts titled apple, which only the ID tiebreak keeps apart
+    let mut paged = Vec::new();
+    let mut after = String::new();
+    for _ in 0..3 {
+        let (indexes, end_cursor) = list(&mut reader, &ids, &format!("({}, first: 2{})", order, after)).await;
+        paged.extend(indexes);
+        if let Some(end_cursor) = end_cursor.as_str() {
+            after = format!(", after: \"{}\"", end_cursor);
+        }
+    }
+    assert_eq!(paged, all);
+
+    // and with the boundary just before them
+    let (indexes, end_cursor) = list(&mut reader, &ids, &format!("({}, first: 3)", order)).await;
+    assert_eq!(indexes, all[..3]);
+    let (indexes, _) = list(&mut reader, &ids, &format!("({}, after: {})", order, end_cursor)).await;
+    assert_eq!(indexes, all[3..]);
+}
@@ -0,0 +1,66 @@
+use serde_json::{json, Value};
+
+use learning_graphql::entity::sea_orm_active_enums::Role;
+
+mod common;
+
+use common::{error_messages, TestApp, TestClient};
+
+// runs the status mutation `mutation` on the post `id`, returning its status and `publishedAt`
+async fn set_status(client: &mut TestClient<'_>, mutation: &str, id: &str) -> (String, Value) {
+    let query = format!("mutation($id: String!) {{ {}(id: $id) {{ status publishedAt }} }}", mutation);
+    let res = client.graphql(&query, json!({ "id": id })).await;
+    assert_eq!(error_messages(&res), Vec::<String>::new());
+    let post = &res["data"][mutation];
+    (post["status"].as_str().expect("status").to_string(), post["publishedAt"].clone())
+}
+
+async fn public_titles(app: &TestApp) -> Value {
+    app.client().graphql("{ posts { nodes { title } } }", json!({})).await["data"]["posts"]["nodes"].clone()
+}
+
+#[actix_web::test]
+async fn unpublished_posts_get_a_new_publication_time() {
+    let app = TestApp::start().await;
+    let (mut author, _) = app.user(Role::Author).await;
+    let res = author.graphql("mutation { createPost(title: \"Round trip\", content: \"Text\") { id publishedAt } }", json!({})).await;
+    assert_eq!(res["data"]["createPost"]["publishedAt"], Value::Null);
+    let id = res["data"]["createPost"]["id"].as_str().expect("post is created").to_string();
+
+    let (status, first_published_at) = set_status(&mut author, "publishPost", &id).await;
+    assert_eq!(status, "PUBLISHED");
+    assert!(first_published_at.is_string());
+    assert_eq!(public_titles(&app).await, json!([{ "title": "Round trip" }]));
+
+    // publishing again does not bump the time
+    assert_eq!(set_status(&mut author, "publishPost", &id).await, ("PUBLISHED".to_string(), first_published_at.clone()));
+
+    assert_eq!(set_status(&mut author, "unpublishPost", &id).await, ("DRAFT".to_string(), Value::Null));
+    assert_eq!(public_titles(&app).await, json!([]));
+
+    let (status, published_at) = set_status(&mut author, "publishPost", &id).await;
+    assert_eq!(status, "PUBLISHED");
+    assert!(published_at.as_str() > first_published_at.as_str(), "{} is not after {}", published_at, first_published_at);
+    assert_eq!(public_titles(&app).await, json!([{ "title": "Round trip" }]));
+
+    // an archived post keeps the time it was published
+    assert_eq!(set_status(&mut author, "archivePost", &id).await, ("ARCHIVED".to_string(), published_at));
+    assert_eq!(public_titles(&app).await, json!([]));
+}
+
+#[actix_web::test]
+async fn archived_scheduled_posts_have_no_publication_time() {
+    let app = TestApp::start().await;
+    let (mut author, _) = app.user(Role::Author).await;
+    let res = author.graphql("mutation { createPost(title: \"Later\", content: \"Text\") { id } }", json!({})).await;
+    let id = res["data"]["createPost"]["id"].as_str().expect("post is created").to_string();
+
+    let at = "2999-01-01T00:00:00";
+    let res = author.graphql(
+        "mutation($id: String!, $at: NaiveDateTime!) { schedulePost(id: $id, at: $at) { status publishedAt } }",
+        json!({ "id": id, "at": at }),
+    ).await;
+    assert_eq!(res["data"]["schedulePost"], json!({ "status": "SCHEDULED", "publishedAt": at }));
+
+    assert_eq!(set_status(&mut author, "archivePost", &id).await, ("ARCHIVED".to_string(), Value::Null));
+}
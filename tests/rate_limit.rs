@@ -0,0 +1,69 @@
+use actix_web::http::{header, StatusCode};
+use serde_json::{json, Value};
+
+use learning_graphql::entity::sea_orm_active_enums::Role;
+
+mod common;
+
+use common::{config, TestApp};
+
+#[actix_web::test]
+async fn registration_starts_are_limited_by_address() {
+    let mut config = config();
+    config.rate_limits.auth_ip_burst = 3;
+    config.rate_limits.auth_ip_rate_per_minute = 1.0;
+    let app = TestApp::start_with(config).await;
+
+    let mut client = app.client();
+    for _ in 0..3 {
+        assert_eq!(client.post("/auth/register/start", &Value::Null).await.0, StatusCode::OK);
+    }
+    // another client from the same address shares the bucket
+    let res = app.server.post("/auth/register/start").send_json(&Value::Null).await.expect("request is sent");
+    assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+    let retry_after: u64 = res.headers().get(header::RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok())
+        .expect("Retry-After in seconds");
+    assert!((1..=60).contains(&retry_after), "Retry-After: {}", retry_after);
+}
+
+#[actix_web::test]
+async fn login_starts_are_limited_by_user() {
+    let mut config = config();
+    config.rate_limits.auth_user_burst = 5;
+    config.rate_limits.auth_user_rate_per_minute = 1.0;
+    let app = TestApp::start_with(config).await;
+    let (_, user_id) = app.user(Role::Reader).await;
+
+    // the registration took no token of the user
+    let mut attacker = app.client();
+    for _ in 0..5 {
+        let (status, _) = attacker.post("/auth/auth/start", &json!(user_id)).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+    let (status, _) = attacker.post("/auth/auth/start", &json!(user_id)).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[actix_web::test]
+async fn failed_logins_lock_the_user_out() {
+    let mut config = config();
+    config.rate_limits.lockout_after = 2;
+    let app = TestApp::start_with(config).await;
+    let mut user = app.client();
+    let user_id = user.register().await;
+
+    // a passkey's answer to its own challenge fails in the login of another user
+    let mut intruder = app.client();
+    let intruder_id = intruder.register().await;
+    for _ in 0..2 {
+        let credential = intruder.authentication_response(intruder_id).await.expect("intruder answers own challenge");
+        assert_eq!(intruder.post("/auth/auth/start", &json!(user_id)).await.0, StatusCode::OK);
+        assert_ne!(intruder.post("/auth/auth/finish", &credential).await.0, StatusCode::OK);
+    }
+
+    // locked out even with the right passkey, until the lockout ends
+    assert_eq!(user.login(user_id).await, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(intruder.login(intruder_id).await, StatusCode::OK);
+}
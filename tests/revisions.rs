@@ -0,0 +1,125 @@
+use sea_orm::prelude::*;
+use serde_json::{json, Value};
+
+use learning_graphql::entity::{post_revision, sea_orm_active_enums::Role};
+
+mod common;
+
+use common::{error_messages, TestApp, TestClient};
+
+const REVISIONS: &str = "query($slug: String!) { post(slug: $slug) { post { revisions { id title slug content } } } }";
+
+async fn create_post(author: &mut TestClient<'_>) -> String {
+    let res = author.graphql("mutation { createPost(title: \"First\", content: \"Line one\\nLine two\\n\") { id } }", json!({})).await;
+    res["data"]["createPost"]["id"].as_str().expect("post is created").to_string()
+}
+
+async fn update_post(author: &mut TestClient<'_>, id: &str) {
+    let res = author.graphql(
+        "mutation($id: String!) { updatePost(id: $id, title: \"Second\", slug: \"second\", content: \"Line one\\nLine 2\\n\") { slug } }",
+        json!({ "id": id }),
+    ).await;
+    assert_eq!(error_messages(&res), Vec::<String>::new());
+}
+
+// the revisions of the post of `slug` as title, slug and content, and their IDs
+async fn revisions(author: &mut TestClient<'_>, slug: &str) -> (Vec<Value>, Vec<String>) {
+    let res = author.graphql(REVISIONS, json!({ "slug": slug })).await;
+    assert_eq!(error_messages(&res), Vec::<String>::new());
+    let revisions = res["data"]["post"]["post"]["revisions"].as_array().expect("revisions").clone();
+    let ids = revisions.iter().map(|revision| revision["id"].as_str().expect("revision ID").to_string()).collect();
+    let states = revisions.into_iter()
+        .map(|revision| json!({ "title": revision["title"], "slug": revision["slug"], "content": revision["content"] }))
+        .collect();
+    (states, ids)
+}
+
+fn first() -> Value {
+    json!({ "title": "First", "slug": "first", "content": "Line one\nLine two\n" })
+}
+
+fn second() -> Value {
+    json!({ "title": "Second", "slug": "second", "content": "Line one\nLine 2\n" })
+}
+
+#[actix_web::test]
+async fn updates_keep_the_previous_state() {
+    let app = TestApp::start().await;
+    let (mut author, _) = app.user(Role::Author).await;
+    let id = create_post(&mut author).await;
+    update_post(&mut author, &id).await;
+    assert_eq!(revisions(&mut author, "second").await.0, vec![first(), second()]);
+
+    // a post from before revisions existed gets its state before the update as the first revision
+    let id = create_post(&mut author).await;
+    post_revision::Entity::delete_many()
+        .filter(post_revision::Column::PostId.eq(Uuid::parse_str(&id).expect("post ID is a UUID")))
+        .exec(&app.conn).await.expect("revisions are deleted");
+    let res = author.graphql(
+        "mutation($id: String!) { updatePost(id: $id, title: \"Third\") { slug } }",
+        json!({ "id": id }),
+    ).await;
+    assert_eq!(res["data"]["updatePost"]["slug"], json!("first-2"));
+    let (states, _) = revisions(&mut author, "first-2").await;
+    assert_eq!(states, vec![
+        json!({ "title": "First", "slug": "first-2", "content": "Line one\nLine two\n" }),
+        json!({ "title": "Third", "slug": "first-2", "content": "Line one\nLine two\n" }),
+    ]);
+}
+
+#[actix_web::test]
+async fn diffs_are_unified() {
+    let app = TestApp::start().await;
+    let (mut author, _) = app.user(Role::Author).await;
+    let id = create_post(&mut author).await;
+    update_post(&mut author, &id).await;
+    let (_, ids) = revisions(&mut author, "second").await;
+
+    let res = author.graphql(
+        "query($from: String!, $to: String!) { post(slug: \"second\") { post { revisionDiff(from: $from, to: $to) } } }",
+        json!({ "from": ids[0], "to": ids[1] }),
+    ).await;
+    assert_eq!(error_messages(&res), Vec::<String>::new());
+    let expected = [
+        format!("--- {}", ids[0]),
+        format!("+++ {}", ids[1]),
+        "@@ -1,5 +1,5 @@".to_string(),
+        "-title: First".to_string(),
+        "-slug: first".to_string(),
+        "+title: Second".to_string(),
+        "+slug: second".to_string(),
+        // the blank line between the header and the content, as context
+        " ".to_string(),
+        " Line one".to_string(),
+        "-Line two".to_string(),
+        "+Line 2".to_string(),
+        String::new(),
+    ].join("\n");
+    assert_eq!(res["data"]["post"]["post"]["revisionDiff"], json!(expected));
+
+    let res = author.graphql(
+        "query($id: String!) { post(slug: \"second\") { post { revisionDiff(from: $id, to: $id) } } }",
+        json!({ "id": ids[0] }),
+    ).await;
+    assert_eq!(res["data"]["post"]["post"]["revisionDiff"], json!(""));
+}
+
+#[actix_web::test]
+async fn restoring_adds_a_revision() {
+    let app = TestApp::start().await;
+    let (mut author, _) = app.user(Role::Author).await;
+    let id = create_post(&mut author).await;
+    update_post(&mut author, &id).await;
+    let (_, ids) = revisions(&mut author, "second").await;
+
+    let res = author.graphql(
+        "mutation($postId: String!, $revisionId: String!) { restorePostRevision(postId: $postId, revisionId: $revisionId) { title slug content } }",
+        json!({ "postId": id, "revisionId": ids[0] }),
+    ).await;
+    assert_eq!(error_messages(&res), Vec::<String>::new());
+    assert_eq!(res["data"]["restorePostRevision"], first());
+
+    let (states, restored_ids) = revisions(&mut author, "first").await;
+    assert_eq!(states, vec![first(), second(), first()]);
+    assert_eq!(restored_ids[..2], ids[..]);
+}
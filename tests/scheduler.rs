@@ -0,0 +1,71 @@
+use chrono::{Duration, NaiveDateTime, Utc};
+use sea_orm::prelude::*;
+use sea_orm::ActiveValue::Set;
+use serde_json::json;
+
+use learning_graphql::{
+    entity::{post, sea_orm_active_enums::{PostStatus, Role}},
+    scheduler,
+};
+
+mod common;
+
+use common::{error_messages, TestApp, TestClient};
+
+// a post of `author` scheduled for `at`, which must be in the future
+async fn scheduled_post(author: &mut TestClient<'_>, title: &str, at: NaiveDateTime) -> Uuid {
+    let res = author.graphql(
+        "mutation($title: String!) { createPost(title: $title, content: \"Text\") { id } }",
+        json!({ "title": title }),
+    ).await;
+    let id = res["data"]["createPost"]["id"].as_str().expect("post is created").to_string();
+    let res = author.graphql(
+        "mutation($id: String!, $at: NaiveDateTime!) { schedulePost(id: $id, at: $at) { status } }",
+        json!({ "id": id, "at": at }),
+    ).await;
+    assert_eq!(error_messages(&res), Vec::<String>::new());
+    assert_eq!(res["data"]["schedulePost"]["status"], "SCHEDULED");
+    Uuid::parse_str(&id).expect("post ID is a UUID")
+}
+
+async fn load(app: &TestApp, id: Uuid) -> post::Model {
+    post::Entity::find_by_id(id).one(&app.conn).await.expect("query runs").expect("post exists")
+}
+
+#[actix_web::test]
+async fn due_posts_are_published() {
+    let app = TestApp::start().await;
+    let (mut author, _) = app.user(Role::Author).await;
+    let id = scheduled_post(&mut author, "Due", Utc::now().naive_utc() + Duration::hours(1)).await;
+    // the time passes
+    let due_at = Utc::now().naive_utc() - Duration::minutes(1);
+    post::ActiveModel { id: Set(id), published_at: Set(Some(due_at)), ..Default::default() }
+        .update(&app.conn).await.expect("post exists");
+
+    assert_eq!(scheduler::publish_scheduled_posts(&app.conn).await.expect("posts are published"), 1);
+    let post = load(&app, id).await;
+    assert_eq!(post.status, PostStatus::Published);
+    assert_eq!(post.published_at, Some(due_at));
+    let res = app.client().graphql("{ posts { nodes { title publishedAt } } }", json!({})).await;
+    assert_eq!(res["data"]["posts"]["nodes"], json!([{ "title": "Due", "publishedAt": due_at }]));
+
+    // published posts are left alone afterwards
+    assert_eq!(scheduler::publish_scheduled_posts(&app.conn).await.expect("posts are published"), 0);
+}
+
+#[actix_web::test]
+async fn future_posts_stay_scheduled() {
+    let app = TestApp::start().await;
+    let (mut author, _) = app.user(Role::Author).await;
+    let at = Utc::now().naive_utc() + Duration::hours(1);
+    let id = scheduled_post(&mut author, "Later", at).await;
+    let before = load(&app, id).await;
+
+    assert_eq!(scheduler::publish_scheduled_posts(&app.conn).await.expect("posts are published"), 0);
+    let post = load(&app, id).await;
+    assert_eq!(post, before);
+    assert_eq!(post.status, PostStatus::Scheduled);
+    assert_eq!(post.published_at, Some(at));
+    let res = app.client().graphql("{ posts { nodes { title } } }", json!({})).await;
+    assert_eq!(res["data"]["posts"]["nodes"], json!([]));
+}
@@ -0,0 +1,36 @@
+use learning_graphql::{schema_builder, schema_diff};
+
+#[test]
+fn schema_matches_snapshot() {
+    let snapshot = include_str!("../schema.graphql");
+    let sdl = schema_builder().finish().sdl();
+    if sdl == snapshot {
+        return;
+    }
+
+    // the changes in meaning, if any, tell what to review, while descriptions and formatting have to match too
+    let changes = schema_diff::diff(snapshot, &sdl).expect("both schemas parse");
+    let changes: Vec<String> = changes.iter().map(ToString::to_string).collect();
+    assert_eq!(
+        sdl, snapshot,
+        "the schema differs from schema.graphql, review the changes and update it with `schema print --output schema.graphql`:\n{}",
+        if changes.is_empty() { "descriptions or formatting only".to_string() } else { changes.join("\n") },
+    );
+}
+
+#[test]
+fn diff_classifies_changes() {
+    let old = "type Query { post(id: ID!): Post posts: [Post!]! } type Post { title: String body: String! }";
+    let new = "type Query { post(id: ID!, draft: Boolean): Post! posts(first: Int!): [Post!]! } type Post { title: String! slug: String }";
+
+    let changes: Vec<String> = schema_diff::diff(old, new).expect("both schemas parse").iter().map(ToString::to_string).collect();
+    assert_eq!(changes, vec![
+        "BREAKING: field `Post.body` was removed",
+        "BREAKING: argument of `Query.posts` `first` was added",
+        "NON-BREAKING: field `Post.title` changed type from `String` to `String!`",
+        "NON-BREAKING: field `Post.slug` was added",
+        "NON-BREAKING: field `Query.post` changed type from `Post` to `Post!`",
+        "NON-BREAKING: argument of `Query.post` `draft` was added",
+    ]);
+    assert_eq!(schema_diff::diff(new, new).expect("schema parses"), Vec::new());
+}
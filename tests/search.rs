@@ -0,0 +1,132 @@
+use sea_orm::{ConnectionTrait, DbBackend, Statement};
+use serde_json::{json, Value};
+
+use learning_graphql::entity::sea_orm_active_enums::Role;
+
+mod common;
+
+use common::{error_messages, TestApp, TestClient};
+
+// creates a post of `author`, published unless `draft`
+async fn create_post(author: &mut TestClient<'_>, title: &str, content: &str, draft: bool) {
+    let res = author.graphql(
+        "mutation($title: String!, $content: String!) { createPost(title: $title, content: $content) { id } }",
+        json!({ "title": title, "content": content }),
+    ).await;
+    let id = res["data"]["createPost"]["id"].as_str().expect("post is created").to_string();
+    if !draft {
+        let res = author.graphql("mutation($id: String!) { publishPost(id: $id) { status } }", json!({ "id": id })).await;
+        assert_eq!(res["data"]["publishPost"]["status"], "PUBLISHED");
+    }
+}
+
+// the posts of the blog searched by each test: two published ones, where the first is about rust the most, and a draft
+async fn create_posts(author: &mut TestClient<'_>) {
+    create_post(author, "Rust ownership", "Ownership and borrowing in Rust. Rust checks borrowing at compile time.", false).await;
+    create_post(author, "Gardening", "Tomatoes in June, and a word on <b>rust</b> on the leaves.", false).await;
+    create_post(author, "Secret", "A draft about rust.", true).await;
+}
+
+// the titles and snippets of the results of `query`, as `client` sees them
+async fn search(client: &mut TestClient<'_>, query: &str) -> Value {
+    let res = client.graphql(
+        "query($query: String!) { searchPosts(query: $query) { nodes { post { title } snippet } } }",
+        json!({ "query": query }),
+    ).await;
+    assert_eq!(error_messages(&res), Vec::<String>::new());
+    res["data"]["searchPosts"]["nodes"].clone()
+}
+
+async fn titles(client: &mut TestClient<'_>, query: &str) -> Vec<String> {
+    search(client, query).await.as_array().expect("results").iter()
+        .map(|result| result["post"]["title"].as_str().expect("title").to_string())
+        .collect()
+}
+
+async fn has_fts(app: &TestApp) -> bool {
+    let table = app.conn.query_one(Statement::from_string(
+        DbBackend::Sqlite,
+        "SELECT name FROM sqlite_master WHERE type = 'table' AND name = 'post_fts'",
+    )).await.expect("query runs");
+    table.is_some()
+}
+
+#[actix_web::test]
+async fn results_are_ranked_with_snippets() {
+    let app = TestApp::start().await;
+    assert!(has_fts(&app).await, "SQLite is built without FTS5");
+    let (mut author, _) = app.user(Role::Author).await;
+    create_posts(&mut author).await;
+
+    let results = search(&mut app.client(), "rust").await;
+    assert_eq!(results, json!([
+        {
+            "post": { "title": "Rust ownership" },
+            "snippet": "Ownership and borrowing in <mark>Rust</mark>. <mark>Rust</mark> checks borrowing at compile time.",
+        },
+        {
+            "post": { "title": "Gardening" },
+            "snippet": "Tomatoes in June, and a word on &lt;b&gt;<mark>rust</mark>&lt;/b&gt; on the leaves.",
+        },
+    ]));
+    assert_eq!(titles(&mut app.client(), "rust tomatoes").await, vec!["Gardening"]);
+    assert_eq!(titles(&mut app.client(), "python").await, Vec::<String>::new());
+}
+
+#[actix_web::test]
+async fn phrases_and_prefixes_match() {
+    let app = TestApp::start().await;
+    let (mut author, _) = app.user(Role::Author).await;
+    create_posts(&mut author).await;
+    let mut reader = app.client();
+
+    assert_eq!(titles(&mut reader, "\"borrowing in rust\"").await, vec!["Rust ownership"]);
+    assert_eq!(titles(&mut reader, "\"rust borrowing\"").await, Vec::<String>::new());
+    assert_eq!(titles(&mut reader, "tomat*").await, vec!["Gardening"]);
+    assert_eq!(titles(&mut reader, "tomat").await, Vec::<String>::new());
+    // FTS5 syntax in the query is matched as text
+    assert_eq!(titles(&mut reader, "rust OR python").await, Vec::<String>::new());
+}
+
+#[actix_web::test]
+async fn drafts_are_found_by_their_author_only() {
+    let app = TestApp::start().await;
+    let (mut author, _) = app.user(Role::Author).await;
+    let (mut other_author, _) = app.user(Role::Author).await;
+    create_posts(&mut author).await;
+
+    assert_eq!(titles(&mut author, "draft").await, vec!["Secret"]);
+    assert_eq!(titles(&mut other_author, "draft").await, Vec::<String>::new());
+    assert_eq!(titles(&mut app.client(), "draft").await, Vec::<String>::new());
+}
+
+#[actix_web::test]
+async fn search_falls_back_without_fts() {
+    let app = TestApp::start().await;
+    // as the migration leaves the database when SQLite has no FTS5
+    app.conn.execute_unprepared("
+        DROP TRIGGER post_fts_insert;
+        DROP TRIGGER post_fts_update;
+        DROP TRIGGER post_fts_delete;
+        DROP TABLE post_fts;
+    ").await.expect("index is dropped");
+    let (mut author, _) = app.user(Role::Author).await;
+    create_posts(&mut author).await;
+    let mut reader = app.client();
+
+    // most recently updated first, with the matches marked in an excerpt of the plain text
+    assert_eq!(search(&mut reader, "rust").await, json!([
+        {
+            "post": { "title": "Gardening" },
+            "snippet": "Tomatoes in June, and a word on <mark>rust</mark> on the leaves.",
+        },
+        {
+            "post": { "title": "Rust ownership" },
+            "snippet": "Ownership and borrowing in <mark>Rust</mark>. <mark>Rust</mark> checks borrowing at compile time.",
+        },
+    ]));
+    assert_eq!(titles(&mut reader, "\"borrowing in rust\"").await, vec!["Rust ownership"]);
+    assert_eq!(titles(&mut reader, "tomat*").await, vec!["Gardening"]);
+    assert_eq!(titles(&mut reader, "draft").await, Vec::<String>::new());
+    assert_eq!(titles(&mut author, "draft").await, vec!["Secret"]);
+}
@@ -0,0 +1,20 @@
+use serde_json::json;
+
+use learning_graphql::entity::sea_orm_active_enums::Role;
+
+mod common;
+
+use common::{error_messages, TestApp};
+
+#[actix_web::test]
+async fn session_is_accepted_by_every_worker() {
+    let app = TestApp::start_with_workers(2).await;
+    let (mut admin, _) = app.user(Role::Admin).await;
+
+    // new connections go to the workers in turn, so the session cookie issued by one worker meets the other
+    for _ in 0..4 {
+        admin.reconnect();
+        let res = admin.graphql("{ users { id } }", json!({})).await;
+        assert_eq!(error_messages(&res), Vec::<String>::new());
+    }
+}
@@ -0,0 +1,68 @@
+use serde_json::{json, Value};
+
+use learning_graphql::entity::sea_orm_active_enums::Role;
+
+mod common;
+
+use common::{error_messages, TestApp, TestClient};
+
+// creates a post titled `title`, returning its ID and slug
+async fn create_post(author: &mut TestClient<'_>, title: &str) -> (String, String) {
+    let res = author.graphql(
+        "mutation($title: String!) { createPost(title: $title, content: \"Text\") { id slug } }",
+        json!({ "title": title }),
+    ).await;
+    assert_eq!(error_messages(&res), Vec::<String>::new());
+    let post = &res["data"]["createPost"];
+    (post["id"].as_str().expect("post is created").to_string(), post["slug"].as_str().expect("slug").to_string())
+}
+
+async fn post_by_slug(client: &mut TestClient<'_>, slug: &str) -> Value {
+    let res = client.graphql(
+        "query($slug: String!) { post(slug: $slug) { post { id slug } redirectTo } }",
+        json!({ "slug": slug }),
+    ).await;
+    assert_eq!(error_messages(&res), Vec::<String>::new());
+    res["data"]["post"].clone()
+}
+
+#[actix_web::test]
+async fn titles_are_transliterated() {
+    let app = TestApp::start().await;
+    let (mut author, _) = app.user(Role::Author).await;
+
+    assert_eq!(create_post(&mut author, "Crème Brûlée à la carte!").await.1, "creme-brulee-a-la-carte");
+    assert_eq!(create_post(&mut author, "Straße über Köln").await.1, "strasse-uber-koln");
+    assert_eq!(create_post(&mut author, "Привет, мир").await.1, "privet-mir");
+    assert_eq!(create_post(&mut author, "?!").await.1, "post");
+}
+
+#[actix_web::test]
+async fn taken_slugs_get_a_suffix() {
+    let app = TestApp::start().await;
+    let (mut author, _) = app.user(Role::Author).await;
+
+    assert_eq!(create_post(&mut author, "Same title").await.1, "same-title");
+    assert_eq!(create_post(&mut author, "Same title").await.1, "same-title-2");
+    assert_eq!(create_post(&mut author, "Same Title?").await.1, "same-title-3");
+}
+
+#[actix_web::test]
+async fn former_slugs_redirect() {
+    let app = TestApp::start().await;
+    let (mut author, _) = app.user(Role::Author).await;
+    let (id, slug) = create_post(&mut author, "Old name").await;
+    assert_eq!(post_by_slug(&mut author, &slug).await, json!({ "post": { "id": id, "slug": "old-name" }, "redirectTo": null }));
+
+    let res = author.graphql(
+        "mutation($id: String!) { updatePost(id: $id, slug: \"New name\") { slug } }",
+        json!({ "id": id }),
+    ).await;
+    assert_eq!(res["data"]["updatePost"]["slug"], json!("new-name"));
+
+    assert_eq!(post_by_slug(&mut author, "old-name").await, json!({ "post": { "id": id, "slug": "new-name" }, "redirectTo": "new-name" }));
+    assert_eq!(post_by_slug(&mut author, "new-name").await, json!({ "post": { "id": id, "slug": "new-name" }, "redirectTo": null }));
+
+    // the former slug stays reserved for the post
+    assert_eq!(create_post(&mut author, "Old name").await.1, "old-name-2");
+}
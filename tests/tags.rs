@@ -0,0 +1,104 @@
+use serde_json::{json, Value};
+
+use learning_graphql::entity::sea_orm_active_enums::Role;
+
+mod common;
+
+use common::{error_messages, TestApp, TestClient};
+
+// creates a post of `author` with `tags`, published unless `draft`, and returns its ID
+async fn tagged_post(author: &mut TestClient<'_>, tags: &[&str], draft: bool) -> String {
+    let res = author.graphql("mutation { createPost(title: \"Tagged\", content: \"Text\") { id } }", json!({})).await;
+    let id = res["data"]["createPost"]["id"].as_str().expect("post is created").to_string();
+    if !draft {
+        let res = author.graphql("mutation($id: String!) { publishPost(id: $id) { status } }", json!({ "id": id })).await;
+        assert_eq!(res["data"]["publishPost"]["status"], "PUBLISHED");
+    }
+    set_tags(author, &id, tags).await;
+    id
+}
+
+async fn set_tags(author: &mut TestClient<'_>, id: &str, tags: &[&str]) -> Value {
+    let res = author.graphql(
+        "mutation($id: String!, $tags: [String!]!) { setPostTags(postId: $id, tags: $tags) { tags { slug name } } }",
+        json!({ "id": id, "tags": tags }),
+    ).await;
+    assert_eq!(error_messages(&res), Vec::<String>::new());
+    res["data"]["setPostTags"]["tags"].clone()
+}
+
+// every tag as slug, ID and post count, as `client` sees them
+async fn tags(client: &mut TestClient<'_>) -> Vec<(String, String, u64)> {
+    let res = client.graphql("{ tags { nodes { id slug postCount } } }", json!({})).await;
+    assert_eq!(error_messages(&res), Vec::<String>::new());
+    res["data"]["tags"]["nodes"].as_array().expect("tags").iter()
+        .map(|tag| (
+            tag["slug"].as_str().expect("slug").to_string(),
+            tag["id"].as_str().expect("ID").to_string(),
+            tag["postCount"].as_u64().expect("post count"),
+        ))
+        .collect()
+}
+
+#[actix_web::test]
+async fn equivalent_names_are_one_tag() {
+    let app = TestApp::start().await;
+    let (mut author, _) = app.user(Role::Author).await;
+    let id = tagged_post(&mut author, &[], false).await;
+
+    // case, compatibility forms (fullwidth letters) and combining accents fold into the same slug
+    let names = ["Rust", "RUST", "ｒｕｓｔ", "Café", "Cafe\u{301}", "  Web Dev! ", "web-dev"];
+    assert_eq!(set_tags(&mut author, &id, &names).await, json!([
+        { "slug": "café", "name": "Café" },
+        { "slug": "rust", "name": "Rust" },
+        { "slug": "web-dev", "name": "Web Dev!" },
+    ]));
+    assert_eq!(tags(&mut author).await.len(), 3);
+
+    let res = author.graphql("{ postsByTag(slug: \"ＣＡＦＥ\u{301}\") { id } }", json!({})).await;
+    assert_eq!(res["data"]["postsByTag"], json!([{ "id": id }]));
+
+    let res = author.graphql(
+        "mutation($id: String!) { setPostTags(postId: $id, tags: [\"rust\", \"?!\"]) { id } }",
+        json!({ "id": id }),
+    ).await;
+    assert_eq!(error_messages(&res), vec!["tag `?!` has no letters or digits"]);
+}
+
+#[actix_web::test]
+async fn missing_tags_are_created() {
+    let app = TestApp::start().await;
+    let (mut author, _) = app.user(Role::Author).await;
+    assert_eq!(tags(&mut author).await, vec![]);
+
+    tagged_post(&mut author, &["rust", "web"], false).await;
+    let before = tags(&mut author).await;
+    assert_eq!(before.iter().map(|(slug, _, _)| slug.as_str()).collect::<Vec<_>>(), vec!["rust", "web"]);
+
+    // existing tags are reused, with their IDs
+    tagged_post(&mut author, &["Web", "graphql"], false).await;
+    let after = tags(&mut author).await;
+    assert_eq!(after.iter().map(|(slug, _, _)| slug.as_str()).collect::<Vec<_>>(), vec!["graphql", "rust", "web"]);
+    assert_eq!(after[1].1, before[0].1);
+    assert_eq!(after[2].1, before[1].1);
+}
+
+#[actix_web::test]
+async fn post_counts_include_visible_posts_only() {
+    let app = TestApp::start().await;
+    let (mut author, _) = app.user(Role::Author).await;
+    let (mut other_author, _) = app.user(Role::Author).await;
+    tagged_post(&mut author, &["rust", "web"], false).await;
+    tagged_post(&mut author, &["rust"], false).await;
+    let draft = tagged_post(&mut author, &["rust", "drafts"], true).await;
+
+    let counts = |tags: Vec<(String, String, u64)>| tags.into_iter().map(|(slug, _, count)| (slug, count)).collect::<Vec<_>>();
+    let expected = |rust, drafts| vec![("drafts".to_string(), drafts), ("rust".to_string(), rust), ("web".to_string(), 1)];
+    assert_eq!(counts(tags(&mut author).await), expected(3, 1));
+    assert_eq!(counts(tags(&mut other_author).await), expected(2, 0));
+    assert_eq!(counts(tags(&mut app.client()).await), expected(2, 0));
+
+    // untagging the draft takes it out of the counts of its author too
+    set_tags(&mut author, &draft, &[]).await;
+    assert_eq!(counts(tags(&mut author).await), expected(2, 0));
+}
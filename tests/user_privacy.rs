@@ -0,0 +1,93 @@
+use serde_json::{json, Value};
+
+use learning_graphql::entity::sea_orm_active_enums::Role;
+
+mod common;
+
+use common::{error_messages, TestApp, TestClient};
+
+// the owner's published post with a comment by the owner, so that anyone can reach the owner as comment author
+async fn publish_with_comment(owner: &mut TestClient<'_>) {
+    let res = owner.graphql("mutation { createPost(title: \"Post\", content: \"Text\") { id } }", json!({})).await;
+    let post_id = res["data"]["createPost"]["id"].as_str().expect("post is created").to_string();
+    let res = owner.graphql("mutation($id: String!) { publishPost(id: $id) { status } }", json!({ "id": post_id })).await;
+    assert_eq!(res["data"]["publishPost"]["status"], "PUBLISHED");
+    let res = owner.graphql(
+        "mutation($postId: String!) { addComment(postId: $postId, body: \"First\") { id } }",
+        json!({ "postId": post_id }),
+    ).await;
+    assert_eq!(error_messages(&res), Vec::<String>::new());
+}
+
+// `selection` of the owner, reached as the author of their comment
+async fn owner_field(client: &mut TestClient<'_>, selection: &str) -> Value {
+    let query = format!("{{ posts {{ nodes {{ comments {{ nodes {{ author {{ id {} }} }} }} }} }} }}", selection);
+    client.graphql(&query, json!({})).await
+}
+
+// whether `client` can read `selection` of the owner, where any error other than a guard's denial fails the test
+async fn can_read(client: &mut TestClient<'_>, selection: &str) -> bool {
+    let errors = error_messages(&owner_field(client, selection).await);
+    assert!(
+        errors.iter().all(|err| err == "unauthenticated" || err == "forbidden"),
+        "unexpected errors reading {}: {:?}", selection, errors,
+    );
+    errors.is_empty()
+}
+
+#[actix_web::test]
+async fn user_fields_by_viewer() {
+    let app = TestApp::start().await;
+    let (mut owner, _) = app.user(Role::Author).await;
+    publish_with_comment(&mut owner).await;
+    let mut anonymous = app.client();
+    let (mut other, _) = app.user(Role::Author).await;
+    let (mut admin, _) = app.user(Role::Admin).await;
+
+    // (selection, anonymous, self, other user, admin)
+    let rules = [
+        ("slug name", true, true, true, true),
+        ("registeredAt", false, true, false, true),
+        ("role", false, true, false, true),
+        ("comment", false, false, false, true),
+        ("passkeys { credentialId }", false, true, false, false),
+        ("sessions { validUntil }", false, true, false, false),
+    ];
+    for (selection, by_anonymous, by_self, by_other, by_admin) in rules {
+        assert_eq!(can_read(&mut anonymous, selection).await, by_anonymous, "{} as anonymous", selection);
+        assert_eq!(can_read(&mut owner, selection).await, by_self, "{} as self", selection);
+        assert_eq!(can_read(&mut other, selection).await, by_other, "{} as other user", selection);
+        assert_eq!(can_read(&mut admin, selection).await, by_admin, "{} as admin", selection);
+    }
+}
+
+#[actix_web::test]
+async fn own_passkeys_and_sessions() {
+    let app = TestApp::start().await;
+    let (mut owner, _) = app.user(Role::Author).await;
+    publish_with_comment(&mut owner).await;
+
+    let res = owner_field(&mut owner, "passkeys { credentialId } sessions { validUntil }").await;
+    let author = &res["data"]["posts"]["nodes"][0]["comments"]["nodes"][0]["author"];
+    assert_eq!(author["passkeys"].as_array().map(Vec::len), Some(1));
+    assert!(author["passkeys"][0]["credentialId"].as_str().is_some_and(|id| !id.is_empty()));
+    assert!(author["sessions"].as_array().is_some_and(|sessions| !sessions.is_empty()));
+}
+
+#[actix_web::test]
+async fn users_are_listed_to_admins_only() {
+    let app = TestApp::start().await;
+    let query = "{ users { id } }";
+
+    let res = app.client().graphql(query, json!({})).await;
+    assert_eq!(error_messages(&res), vec!["unauthenticated"]);
+
+    let (mut author, _) = app.user(Role::Author).await;
+    let res = author.graphql(query, json!({})).await;
+    assert_eq!(error_messages(&res), vec!["forbidden"]);
+
+    let (mut admin, _) = app.user(Role::Admin).await;
+    let res = admin.graphql(query, json!({})).await;
+    assert_eq!(error_messages(&res), Vec::<String>::new());
+    assert_eq!(res["data"]["users"].as_array().map(Vec::len), Some(2));
+}